@@ -0,0 +1,164 @@
+//! Minimal contract used by the `sim` tests, without dependencies.
+//!
+//! Build it from the repository's root with:
+//!
+//! ```sh
+//! rustc +1.60.0 --target wasm32-unknown-unknown --crate-type cdylib \
+//!     -C opt-level=z -C panic=abort -C strip=symbols \
+//!     -o res/test_contract.wasm res/test_contract.rs
+//! ```
+//!
+//! The paths of it's panic locations are embedded in the wasm, so building it
+//! from another directory gives different bytes. The CI checks that the
+//! committed wasm matches this source.
+
+#![no_std]
+
+extern "C" {
+    fn input(register_id: u64);
+    fn register_len(register_id: u64) -> u64;
+    fn read_register(
+        register_id: u64,
+        ptr: u64,
+    );
+    fn predecessor_account_id(register_id: u64);
+    fn storage_write(
+        key_len: u64,
+        key_ptr: u64,
+        value_len: u64,
+        value_ptr: u64,
+        register_id: u64,
+    ) -> u64;
+    fn storage_remove(
+        key_len: u64,
+        key_ptr: u64,
+        register_id: u64,
+    ) -> u64;
+    fn panic_utf8(
+        len: u64,
+        ptr: u64,
+    );
+    fn promise_create(
+        account_id_len: u64,
+        account_id_ptr: u64,
+        method_name_len: u64,
+        method_name_ptr: u64,
+        arguments_len: u64,
+        arguments_ptr: u64,
+        amount_ptr: u64,
+        gas: u64,
+    ) -> u64;
+    fn promise_return(promise_id: u64);
+}
+
+const BUF_LEN: usize = 4096;
+static mut INPUT: [u8; BUF_LEN] = [0; BUF_LEN];
+static mut PREDECESSOR: [u8; 64] = [0; 64];
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    core::arch::wasm32::unreachable()
+}
+
+fn fail(msg: &str) -> ! {
+    unsafe { panic_utf8(msg.len() as u64, msg.as_ptr() as u64) };
+    core::arch::wasm32::unreachable()
+}
+
+/// Reads the register into the buffer, returning it's length.
+unsafe fn read(
+    register_id: u64,
+    buf: &mut [u8],
+) -> usize {
+    let len = register_len(register_id) as usize;
+    if len > buf.len() {
+        fail("register is too large");
+    }
+    read_register(register_id, buf.as_mut_ptr() as u64);
+    len
+}
+
+unsafe fn read_input() -> &'static [u8] {
+    input(0);
+    let len = read(0, &mut INPUT);
+    &INPUT[..len]
+}
+
+unsafe fn predecessor() -> &'static [u8] {
+    predecessor_account_id(0);
+    let len = read(0, &mut PREDECESSOR);
+    &PREDECESSOR[..len]
+}
+
+/// Initializes the contract.
+#[no_mangle]
+pub extern "C" fn new() {
+    let key = b"owner";
+    unsafe {
+        let owner = predecessor();
+        storage_write(
+            key.len() as u64,
+            key.as_ptr() as u64,
+            owner.len() as u64,
+            owner.as_ptr() as u64,
+            0,
+        );
+    }
+}
+
+/// Stores the arguments, as they are, under the predecessor's id.
+#[no_mangle]
+pub extern "C" fn write() {
+    unsafe {
+        let value = read_input();
+        let key = predecessor();
+        storage_write(
+            key.len() as u64,
+            key.as_ptr() as u64,
+            value.len() as u64,
+            value.as_ptr() as u64,
+            1,
+        );
+    }
+}
+
+/// Removes what was stored under the predecessor's id.
+#[no_mangle]
+pub extern "C" fn remove() {
+    unsafe {
+        let key = predecessor();
+        storage_remove(key.len() as u64, key.as_ptr() as u64, 1);
+    }
+}
+
+/// Calls `write` on the contract given as a JSON string argument, with the
+/// same arguments.
+#[no_mangle]
+pub extern "C" fn forward() {
+    let method = b"write";
+    let amount: u128 = 0;
+    unsafe {
+        let args = read_input();
+        if args.len() < 2 || args[0] != b'"' || args[args.len() - 1] != b'"' {
+            fail("expected a JSON string");
+        }
+        let receiver = &args[1..args.len() - 1];
+        let promise = promise_create(
+            receiver.len() as u64,
+            receiver.as_ptr() as u64,
+            method.len() as u64,
+            method.as_ptr() as u64,
+            args.len() as u64,
+            args.as_ptr() as u64,
+            &amount as *const u128 as u64,
+            20_000_000_000_000,
+        );
+        promise_return(promise);
+    }
+}
+
+/// Always panics.
+#[no_mangle]
+pub extern "C" fn fail_always() {
+    fail("it always fails")
+}
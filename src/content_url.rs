@@ -0,0 +1,731 @@
+//! Content-addressed links, such as IPFS and Arweave urls, and links pinned
+//! to the hash of their content.
+//!
+//! Links de/serialize to JSON as their native form (eg. `ipfs://<cid>`) and
+//! for borsh they are considered a `String` of that same form.
+
+use crate::wrapped_url::Url;
+use near_sdk::{
+    borsh::{self, BorshDeserialize, BorshSerialize},
+    env,
+    schemars::{
+        gen::SchemaGenerator,
+        schema::{InstanceType, Metadata, Schema, SchemaObject, StringValidation},
+        JsonSchema,
+    },
+    serde::{Deserialize, Serialize},
+};
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+/// Why a string is not a valid content-addressed link.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContentUrlError {
+    InvalidUrl(url::ParseError),
+    InvalidCid(String),
+    InvalidTxId(String),
+    /// What follows the id doesn't start with `/`, `?` or `#`.
+    InvalidPath(String),
+    /// The url is neither in the native form nor a known gateway form.
+    NotContentAddressed(String),
+    InvalidHash(String),
+}
+
+impl std::fmt::Display for ContentUrlError {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        match self {
+            Self::InvalidUrl(err) => write!(f, "invalid url: {}", err),
+            Self::InvalidCid(cid) => write!(f, "invalid IPFS CID: \"{}\"", cid),
+            Self::InvalidTxId(id) => write!(f, "invalid Arweave transaction id: \"{}\"", id),
+            Self::InvalidPath(path) => write!(f, "invalid path after the id: \"{}\"", path),
+            Self::NotContentAddressed(url) => {
+                write!(f, "\"{}\" is not a native nor a gateway url", url)
+            }
+            Self::InvalidHash(err) => write!(f, "invalid base64 sha256 hash: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ContentUrlError {}
+
+impl From<url::ParseError> for ContentUrlError {
+    fn from(err: url::ParseError) -> Self {
+        Self::InvalidUrl(err)
+    }
+}
+
+/// Splits `<id>[/path]` into the id and the remaining path, query and
+/// fragment (including it's leading separator).
+fn split_id(s: &str) -> (&str, &str) {
+    let end = s.find(&['/', '?', '#'][..]).unwrap_or(s.len());
+    s.split_at(end)
+}
+
+/// The remaining path, query and fragment of a gateway url, after
+/// skipping it's first `skip` path segments.
+fn gateway_rest(
+    url: &url::Url,
+    skip: usize,
+) -> String {
+    let mut rest: String = url
+        .path_segments()
+        .into_iter()
+        .flatten()
+        .skip(skip)
+        .map(|segment| format!("/{}", segment))
+        .collect();
+    if let Some(query) = url.query() {
+        rest.push('?');
+        rest.push_str(query);
+    }
+    if let Some(fragment) = url.fragment() {
+        rest.push('#');
+        rest.push_str(fragment);
+    }
+    rest
+}
+
+/// Whether the url is a http(s) url, as gateways are.
+fn is_http(url: &url::Url) -> bool {
+    matches!(url.scheme(), "http" | "https")
+}
+
+/// Whether the string is a CIDv0 or a CIDv1 in a common multibase encoding.
+fn is_cid(cid: &str) -> bool {
+    const BASE58: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+    let all = |s: &str, f: fn(char) -> bool| s.len() >= 8 && s.chars().all(f);
+
+    if cid.len() == 46 && cid.starts_with("Qm") {
+        return cid.chars().all(|c| BASE58.contains(c));
+    }
+    let mut chars = cid.chars();
+    let prefix = chars.next();
+    let rest = chars.as_str();
+    match prefix {
+        Some('b') => all(rest, |c| matches!(c, 'a'..='z' | '2'..='7')),
+        Some('B') => all(rest, |c| matches!(c, 'A'..='Z' | '2'..='7')),
+        Some('k') => all(rest, |c| matches!(c, 'a'..='z' | '0'..='9')),
+        Some('f') => all(rest, |c| matches!(c, 'a'..='f' | '0'..='9')),
+        Some('z') => rest.len() >= 8 && rest.chars().all(|c| BASE58.contains(c)),
+        _ => false,
+    }
+}
+
+/// Whether the string is an Arweave transaction id (43 base64url chars).
+fn is_tx_id(id: &str) -> bool {
+    id.len() == 43
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+fn string_schema(
+    description: &str,
+    pattern: &str,
+    example: &str,
+) -> Schema {
+    let s_validation = StringValidation {
+        pattern: Some(pattern.into()),
+        ..Default::default()
+    };
+
+    let meta = Metadata {
+        description: Some(description.into()),
+        examples: vec![example.into()],
+        ..Default::default()
+    };
+
+    SchemaObject {
+        instance_type: Some(InstanceType::String.into()),
+        format: Some("uri".into()),
+        metadata: Box::new(meta).into(),
+        string: Some(Box::new(s_validation)),
+        ..Default::default()
+    }
+    .into()
+}
+
+/// Implements serde, borsh and conversions through the link's native
+/// string form.
+macro_rules! impl_string_encoding {
+    ($t:ty) => {
+        impl std::fmt::Display for $t {
+            fn fmt(
+                &self,
+                f: &mut std::fmt::Formatter<'_>,
+            ) -> std::fmt::Result {
+                write!(f, "{}{}{}", Self::SCHEME, self.id, self.path)
+            }
+        }
+
+        impl TryFrom<String> for $t {
+            type Error = ContentUrlError;
+
+            fn try_from(s: String) -> Result<Self, Self::Error> {
+                s.parse()
+            }
+        }
+
+        impl From<$t> for String {
+            fn from(link: $t) -> Self {
+                link.to_string()
+            }
+        }
+
+        impl BorshSerialize for $t {
+            fn serialize<W: std::io::Write>(
+                &self,
+                writer: &mut W,
+            ) -> std::io::Result<()> {
+                borsh::BorshSerialize::serialize(&self.to_string(), writer)
+            }
+        }
+
+        impl BorshDeserialize for $t {
+            fn deserialize(buf: &mut &[u8]) -> std::io::Result<Self> {
+                let s: String = borsh::BorshDeserialize::deserialize(buf)?;
+                s.parse()
+                    .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+            }
+        }
+    };
+}
+
+/// An `ipfs://<cid>[/path]` link.
+///
+/// It can also be parsed from gateway urls, either in the path form
+/// (`https://<gateway>/ipfs/<cid>[/path]`) or in the subdomain form
+/// (`https://<cid>.ipfs.<gateway>[/path]`).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(try_from = "String", into = "String")]
+pub struct IpfsUrl {
+    id: String,
+    path: String,
+}
+
+impl IpfsUrl {
+    pub const SCHEME: &'static str = "ipfs://";
+    pub const DEFAULT_GATEWAY: &'static str = "https://ipfs.io";
+
+    /// The `path` is anything that follows the CID, such as `/a.png`.
+    pub fn new(
+        cid: &str,
+        path: &str,
+    ) -> Result<Self, ContentUrlError> {
+        if !is_cid(cid) {
+            return Err(ContentUrlError::InvalidCid(cid.to_string()));
+        }
+        if !(path.is_empty() || path.starts_with(&['/', '?', '#'][..])) {
+            return Err(ContentUrlError::InvalidPath(path.to_string()));
+        }
+        Ok(Self {
+            id: cid.to_string(),
+            path: path.to_string(),
+        })
+    }
+
+    pub fn cid(&self) -> &str {
+        &self.id
+    }
+
+    /// Anything that follows the CID, such as `/a.png`.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Parses a http(s) gateway url.
+    pub fn from_gateway(url: &url::Url) -> Result<Self, ContentUrlError> {
+        let not_ipfs = || ContentUrlError::NotContentAddressed(url.to_string());
+        if !is_http(url) {
+            return Err(not_ipfs());
+        }
+
+        // path form
+        let mut segments = url.path_segments().into_iter().flatten();
+        if let (Some("ipfs"), Some(cid)) = (segments.next(), segments.next()) {
+            return Self::new(cid, &gateway_rest(url, 2));
+        }
+
+        // subdomain form
+        let host = url.host_str().ok_or_else(not_ipfs)?;
+        let mut labels = host.split('.');
+        match (labels.next(), labels.next()) {
+            (Some(cid), Some("ipfs")) => Self::new(cid, &gateway_rest(url, 0)),
+            _ => Err(not_ipfs()),
+        }
+    }
+
+    /// Creates a gateway url in the path form, such as
+    /// `https://ipfs.io/ipfs/<cid>[/path]`.
+    pub fn to_gateway(
+        &self,
+        gateway: &str,
+    ) -> Result<Url, ContentUrlError> {
+        let url: url::Url = format!(
+            "{}/ipfs/{}{}",
+            gateway.trim_end_matches('/'),
+            self.id,
+            self.path
+        )
+        .parse()?;
+        Ok(url.into())
+    }
+
+    /// Creates a gateway url using [`Self::DEFAULT_GATEWAY`].
+    pub fn to_default_gateway(&self) -> Url {
+        self.to_gateway(Self::DEFAULT_GATEWAY)
+            .expect("the default gateway should be valid")
+    }
+}
+
+impl FromStr for IpfsUrl {
+    type Err = ContentUrlError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix(Self::SCHEME) {
+            Some(rest) => {
+                let (cid, path) = split_id(rest);
+                Self::new(cid, path)
+            }
+            None => Self::from_gateway(&s.parse()?),
+        }
+    }
+}
+
+impl_string_encoding!(IpfsUrl);
+
+impl JsonSchema for IpfsUrl {
+    fn is_referenceable() -> bool {
+        true
+    }
+    fn schema_name() -> String {
+        ("IpfsUrl").to_owned()
+    }
+    fn json_schema(_gen: &mut SchemaGenerator) -> Schema {
+        string_schema(
+            "IPFS link, in the form `ipfs://<cid>[/path]`.",
+            "^ipfs://[0-9A-Za-z]+([/?#].*)?$",
+            "ipfs://bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi/a.png",
+        )
+    }
+}
+
+/// An `ar://<txid>[/path]` link.
+///
+/// It can also be parsed from gateway urls in the form
+/// `https://<gateway>/<txid>[/path]`, from one of the
+/// [`GATEWAYS`](Self::GATEWAYS) or their subdomains.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(try_from = "String", into = "String")]
+pub struct ArweaveUrl {
+    id: String,
+    path: String,
+}
+
+impl ArweaveUrl {
+    pub const SCHEME: &'static str = "ar://";
+    pub const DEFAULT_GATEWAY: &'static str = "https://arweave.net";
+    /// Hosts of the known gateways. Unlike IPFS gateway urls, any url could
+    /// have a transaction id as it's first path segment, so only these are
+    /// considered gateways.
+    pub const GATEWAYS: &'static [&'static str] = &["arweave.net", "ar-io.net", "arweave.dev"];
+
+    /// The `path` is anything that follows the transaction id, such as
+    /// `/a.png`.
+    pub fn new(
+        tx_id: &str,
+        path: &str,
+    ) -> Result<Self, ContentUrlError> {
+        if !is_tx_id(tx_id) {
+            return Err(ContentUrlError::InvalidTxId(tx_id.to_string()));
+        }
+        if !(path.is_empty() || path.starts_with(&['/', '?', '#'][..])) {
+            return Err(ContentUrlError::InvalidPath(path.to_string()));
+        }
+        Ok(Self {
+            id: tx_id.to_string(),
+            path: path.to_string(),
+        })
+    }
+
+    pub fn tx_id(&self) -> &str {
+        &self.id
+    }
+
+    /// Anything that follows the transaction id, such as `/a.png`.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Parses a http(s) url of one of the [`GATEWAYS`](Self::GATEWAYS).
+    pub fn from_gateway(url: &url::Url) -> Result<Self, ContentUrlError> {
+        let is_gateway = |host: &str| {
+            Self::GATEWAYS.iter().any(|gateway| {
+                host == *gateway
+                    || host
+                        .strip_suffix(gateway)
+                        .map_or(false, |sub| sub.ends_with('.'))
+            })
+        };
+        match url.path_segments().and_then(|mut s| s.next()) {
+            Some(tx_id)
+                if is_tx_id(tx_id) && is_http(url) && url.host_str().map_or(false, is_gateway) =>
+            {
+                Self::new(tx_id, &gateway_rest(url, 1))
+            }
+            _ => Err(ContentUrlError::NotContentAddressed(url.to_string())),
+        }
+    }
+
+    /// Creates a gateway url, such as `https://arweave.net/<txid>[/path]`.
+    pub fn to_gateway(
+        &self,
+        gateway: &str,
+    ) -> Result<Url, ContentUrlError> {
+        let url: url::Url =
+            format!("{}/{}{}", gateway.trim_end_matches('/'), self.id, self.path).parse()?;
+        Ok(url.into())
+    }
+
+    /// Creates a gateway url using [`Self::DEFAULT_GATEWAY`].
+    pub fn to_default_gateway(&self) -> Url {
+        self.to_gateway(Self::DEFAULT_GATEWAY)
+            .expect("the default gateway should be valid")
+    }
+}
+
+impl FromStr for ArweaveUrl {
+    type Err = ContentUrlError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix(Self::SCHEME) {
+            Some(rest) => {
+                let (tx_id, path) = split_id(rest);
+                Self::new(tx_id, path)
+            }
+            None => Self::from_gateway(&s.parse()?),
+        }
+    }
+}
+
+impl_string_encoding!(ArweaveUrl);
+
+impl JsonSchema for ArweaveUrl {
+    fn is_referenceable() -> bool {
+        true
+    }
+    fn schema_name() -> String {
+        ("ArweaveUrl").to_owned()
+    }
+    fn json_schema(_gen: &mut SchemaGenerator) -> Schema {
+        string_schema(
+            "Arweave link, in the form `ar://<txid>[/path]`.",
+            "^ar://[0-9A-Za-z_-]{43}([/?#].*)?$",
+            "ar://bNbA3TEQVL60xlgCcqdz4ZPHFZ711cZ3hmkpGttDt_U",
+        )
+    }
+}
+
+/// A sha256 hash.
+///
+/// It de/serializes to JSON as a base64 string, and for borsh the structure
+/// is considered a `[u8; 32]`.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    BorshSerialize,
+    BorshDeserialize,
+    Serialize,
+    Deserialize,
+)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(try_from = "String", into = "String")]
+pub struct Sha256Hash(pub [u8; 32]);
+
+impl Sha256Hash {
+    /// Hashes the `content`.
+    pub fn of(content: &[u8]) -> Self {
+        let mut hash = [0u8; 32];
+        hash.copy_from_slice(&env::sha256(content));
+        Self(hash)
+    }
+}
+
+impl FromStr for Sha256Hash {
+    type Err = ContentUrlError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes =
+            base64::decode(s).map_err(|err| ContentUrlError::InvalidHash(err.to_string()))?;
+        let hash = <[u8; 32]>::try_from(bytes.as_slice()).map_err(|_| {
+            ContentUrlError::InvalidHash(format!("expected 32 bytes, got {}", bytes.len()))
+        })?;
+        Ok(Self(hash))
+    }
+}
+
+impl std::fmt::Display for Sha256Hash {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        f.write_str(&base64::encode(self.0))
+    }
+}
+
+impl TryFrom<String> for Sha256Hash {
+    type Error = ContentUrlError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Sha256Hash> for String {
+    fn from(hash: Sha256Hash) -> Self {
+        hash.to_string()
+    }
+}
+
+impl JsonSchema for Sha256Hash {
+    fn is_referenceable() -> bool {
+        true
+    }
+    fn schema_name() -> String {
+        ("Sha256Hash").to_owned()
+    }
+    fn json_schema(_gen: &mut SchemaGenerator) -> Schema {
+        let s_validation = StringValidation {
+            max_length: Some(44),
+            min_length: Some(44),
+            pattern: Some("^[0-9A-Za-z+/]{43}=$".into()),
+        };
+
+        let meta = Metadata {
+            description: Some("Base64-encoded sha256 hash.".into()),
+            ..Default::default()
+        };
+
+        SchemaObject {
+            instance_type: Some(InstanceType::String.into()),
+            metadata: Box::new(meta).into(),
+            string: Some(Box::new(s_validation)),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+/// A link which may be pinned to the hash of it's content, similarly to
+/// the `media`/`media_hash` pair of NEP-177.
+///
+/// For JSON it's an object with the `link` and an optional `hash`, and for
+/// borsh the structure is considered a `(L, Option<Sha256Hash>)`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(crate = "near_sdk::serde")]
+#[schemars(crate = "near_sdk::schemars")]
+pub struct HashedLink<L> {
+    pub link: L,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<Sha256Hash>,
+}
+
+/// A [`Url`] pinned to the hash of it's content.
+pub type PinnedUrl = HashedLink<Url>;
+
+impl<L> HashedLink<L> {
+    pub fn new(
+        link: L,
+        hash: impl Into<Option<Sha256Hash>>,
+    ) -> Self {
+        Self {
+            link,
+            hash: hash.into(),
+        }
+    }
+
+    /// Checks the `content` against the hash.
+    ///
+    /// Returns `true` if there is no hash to check against.
+    pub fn verify(
+        &self,
+        content: &[u8],
+    ) -> bool {
+        match &self.hash {
+            Some(hash) => *hash == Sha256Hash::of(content),
+            None => true,
+        }
+    }
+}
+
+impl<L: BorshSerialize> BorshSerialize for HashedLink<L> {
+    fn serialize<W: std::io::Write>(
+        &self,
+        writer: &mut W,
+    ) -> std::io::Result<()> {
+        borsh::BorshSerialize::serialize(&self.link, writer)?;
+        borsh::BorshSerialize::serialize(&self.hash, writer)
+    }
+}
+
+impl<L: BorshDeserialize> BorshDeserialize for HashedLink<L> {
+    fn deserialize(buf: &mut &[u8]) -> std::io::Result<Self> {
+        Ok(Self {
+            link: borsh::BorshDeserialize::deserialize(buf)?,
+            hash: borsh::BorshDeserialize::deserialize(buf)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::serde_json;
+
+    const CID: &str = "bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi";
+    const CID_V0: &str = "QmT78zSuBmuS4z925WZfrqQ1qHaJ56DQaTfyMUF7F8ff5o";
+    const TX_ID: &str = "bNbA3TEQVL60xlgCcqdz4ZPHFZ711cZ3hmkpGttDt_U";
+
+    #[test]
+    fn ipfs() {
+        let native = format!("ipfs://{}/a/b.png", CID);
+        let link: IpfsUrl = native.parse().unwrap();
+        assert_eq!(link.cid(), CID);
+        assert_eq!(link.path(), "/a/b.png");
+        assert_eq!(link.to_string(), native);
+
+        let gateway = link.to_default_gateway();
+        assert_eq!(
+            url::Url::from(gateway.clone()).as_str(),
+            format!("https://ipfs.io/ipfs/{}/a/b.png", CID)
+        );
+        let from_gateway: IpfsUrl = url::Url::from(gateway).as_str().parse().unwrap();
+        assert_eq!(from_gateway, link);
+
+        let subdomain: IpfsUrl = format!("https://{}.ipfs.dweb.link/a/b.png", CID)
+            .parse()
+            .unwrap();
+        assert_eq!(subdomain, link);
+
+        let v0: IpfsUrl = format!("ipfs://{}", CID_V0).parse().unwrap();
+        assert_eq!(v0.cid(), CID_V0);
+        assert_eq!(v0.path(), "");
+
+        assert_eq!(
+            "ipfs://Qm0".parse::<IpfsUrl>(),
+            Err(ContentUrlError::InvalidCid("Qm0".into()))
+        );
+        assert!(matches!(
+            "https://example.com/a.png".parse::<IpfsUrl>(),
+            Err(ContentUrlError::NotContentAddressed(_))
+        ));
+        assert!(matches!(
+            format!("ftp://example.com/ipfs/{}", CID).parse::<IpfsUrl>(),
+            Err(ContentUrlError::NotContentAddressed(_))
+        ));
+        assert_eq!(
+            IpfsUrl::new(CID, "a.png"),
+            Err(ContentUrlError::InvalidPath("a.png".into()))
+        );
+    }
+
+    #[test]
+    fn arweave() {
+        let link: ArweaveUrl = format!("https://arweave.net/{}/a.png?b=c", TX_ID)
+            .parse()
+            .unwrap();
+        assert_eq!(link.tx_id(), TX_ID);
+        assert_eq!(link.path(), "/a.png?b=c");
+        assert_eq!(link.to_string(), format!("ar://{}/a.png?b=c", TX_ID));
+        assert_eq!(link.to_string().parse::<ArweaveUrl>().unwrap(), link);
+
+        assert_eq!(
+            "ar://short".parse::<ArweaveUrl>(),
+            Err(ContentUrlError::InvalidTxId("short".into()))
+        );
+        assert_eq!(
+            ArweaveUrl::new(TX_ID, "a.png"),
+            Err(ContentUrlError::InvalidPath("a.png".into()))
+        );
+
+        let sandbox: ArweaveUrl = format!("https://sandbox.arweave.net/{}", TX_ID)
+            .parse()
+            .unwrap();
+        assert_eq!(sandbox.tx_id(), TX_ID);
+        for url in [
+            format!("ftp://arweave.net/{}", TX_ID),
+            format!("https://evil.example/{}", TX_ID),
+            format!("https://evilarweave.net/{}", TX_ID),
+        ] {
+            assert!(
+                matches!(
+                    url.parse::<ArweaveUrl>(),
+                    Err(ContentUrlError::NotContentAddressed(_))
+                ),
+                "{}",
+                url
+            );
+        }
+    }
+
+    #[test]
+    fn verify() {
+        // sha256 of empty content
+        let empty: Sha256Hash = "47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU="
+            .parse()
+            .unwrap();
+        assert_eq!(Sha256Hash::of(b""), empty);
+        assert_ne!(Sha256Hash::of(b"a"), empty);
+
+        let link: IpfsUrl = format!("ipfs://{}", CID).parse().unwrap();
+        let pinned = HashedLink::new(link.clone(), Sha256Hash::of(b"content"));
+        assert!(pinned.verify(b"content"));
+        assert!(!pinned.verify(b"other content"));
+        assert!(HashedLink::new(link, None).verify(b"anything"));
+    }
+
+    #[test]
+    fn encoding() {
+        let link: IpfsUrl = format!("ipfs://{}", CID).parse().unwrap();
+        let hashed = HashedLink::new(link.clone(), Sha256Hash([1; 32]));
+
+        let json = serde_json::to_value(&hashed).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "link": format!("ipfs://{}", CID),
+                "hash": base64::encode([1; 32]),
+            })
+        );
+        assert_eq!(
+            serde_json::from_value::<HashedLink<IpfsUrl>>(json).unwrap(),
+            hashed
+        );
+
+        let bytes = hashed.try_to_vec().unwrap();
+        let mut expected = link.to_string().try_to_vec().unwrap();
+        expected.push(1);
+        expected.extend([1; 32]);
+        assert_eq!(bytes, expected);
+        assert_eq!(
+            HashedLink::<IpfsUrl>::try_from_slice(&bytes).unwrap(),
+            hashed
+        );
+
+        let unhashed = HashedLink::new(link, None);
+        assert_eq!(
+            serde_json::to_value(&unhashed).unwrap(),
+            serde_json::json!({ "link": format!("ipfs://{}", CID) })
+        );
+        assert!(serde_json::from_str::<Sha256Hash>(r#""AQID""#).is_err());
+    }
+}
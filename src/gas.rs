@@ -0,0 +1,197 @@
+//! Gas budgeting for cross-contract calls.
+//!
+//! See [`GasBudget`].
+
+use near_sdk::{env, require, Gas};
+
+/// Amount of gas in one TGas.
+pub const TGAS: u64 = 1_000_000_000_000;
+
+/// Stringifies some gas amount into it's TGas representation, ignoring
+/// what's below milliTGas.
+///
+/// Examples:
+/// - 5000000000000 => "5 TGas"
+/// - 5001000000000 => "~5.001 TGas"
+pub fn format_gas(gas: Gas) -> String {
+    let tgas = gas.0 / TGAS;
+    let rem = gas.0 % TGAS;
+    if rem == 0 {
+        format!("{} TGas", tgas)
+    } else {
+        // ignore whats below milli_tera
+        let milli_tera = TGAS / 1000;
+        let milli_tera_gas = rem / milli_tera;
+
+        format!("~{}.{:0>3} TGas", tgas, milli_tera_gas)
+    }
+}
+
+/// A step that receives a share of the gas distributed by
+/// [`GasBudget::distribute`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct GasShare<'a> {
+    /// Name used in the error message when the step doesn't get at least
+    /// `min` gas.
+    pub name: &'a str,
+    /// Relative weight of the step.
+    pub weight: u64,
+    /// Minimum amount of gas the step requires.
+    pub min: Gas,
+}
+
+impl<'a> GasShare<'a> {
+    pub fn new(
+        name: &'a str,
+        weight: u64,
+        min: Gas,
+    ) -> Self {
+        Self { name, weight, min }
+    }
+}
+
+/// Splits an amount of gas among outgoing promises and their callbacks.
+///
+/// Fixed amounts are first reserved (eg. for callbacks), and then the
+/// remainder is distributed by weight.
+///
+/// Example:
+/// ```ignore
+/// let mut budget = GasBudget::from_prepaid(Gas(5 * TGAS));
+/// let resolve_gas = budget.reserve("ft_resolve_transfer", Gas(5 * TGAS));
+/// let [transfer_gas, log_gas]: [Gas; 2] = budget
+///     .distribute(&[
+///         GasShare::new("ft_on_transfer", 3, Gas(10 * TGAS)),
+///         GasShare::new("log", 1, Gas(TGAS)),
+///     ])
+///     .try_into()
+///     .unwrap();
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct GasBudget {
+    remaining: Gas,
+}
+
+impl GasBudget {
+    pub fn new(total: Gas) -> Self {
+        Self { remaining: total }
+    }
+
+    /// Creates a budget from the prepaid gas that was not yet used, except
+    /// for `keep`, which is left for the current function to finish it's
+    /// execution.
+    pub fn from_prepaid(keep: Gas) -> Self {
+        let available = env::prepaid_gas().0.saturating_sub(env::used_gas().0);
+        require!(
+            available >= keep.0,
+            format!(
+                "Not enough gas: requires at least {}, {} available",
+                format_gas(keep),
+                format_gas(Gas(available))
+            )
+        );
+        Self::new(Gas(available - keep.0))
+    }
+
+    /// Gas that is yet to be reserved or distributed.
+    pub fn remaining(&self) -> Gas {
+        self.remaining
+    }
+
+    /// Takes a fixed amount of gas from the budget.
+    ///
+    /// Panics if the budget doesn't have enough gas for the `step`.
+    pub fn reserve(
+        &mut self,
+        step: &str,
+        gas: Gas,
+    ) -> Gas {
+        require!(
+            self.remaining.0 >= gas.0,
+            format!(
+                "Not enough gas for {}: requires {}, {} remaining",
+                step,
+                format_gas(gas),
+                format_gas(self.remaining)
+            )
+        );
+        self.remaining = Gas(self.remaining.0 - gas.0);
+        gas
+    }
+
+    /// Distributes all of the remaining gas among the `shares`,
+    /// proportionally to their weights.
+    ///
+    /// What is lost on the integer division goes to the last share.  
+    /// Panics if any share gets less than it's minimum.
+    pub fn distribute(
+        self,
+        shares: &[GasShare],
+    ) -> Vec<Gas> {
+        let total_weight: u128 = shares.iter().map(|s| s.weight as u128).sum();
+        require!(total_weight > 0, "Gas shares must have a positive weight");
+
+        let remaining = self.remaining.0 as u128;
+        let mut gas: Vec<u64> = shares
+            .iter()
+            .map(|s| (remaining * s.weight as u128 / total_weight) as u64)
+            .collect();
+        let distributed: u64 = gas.iter().sum();
+        if let Some(last) = gas.last_mut() {
+            *last += self.remaining.0 - distributed;
+        }
+
+        for (share, gas) in shares.iter().zip(gas.iter()) {
+            require!(
+                *gas >= share.min.0,
+                format!(
+                    "Not enough gas for {}: requires at least {}, got {}",
+                    share.name,
+                    format_gas(share.min),
+                    format_gas(Gas(*gas))
+                )
+            );
+        }
+
+        gas.into_iter().map(Gas).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format() {
+        assert_eq!(format_gas(Gas(5 * TGAS)), "5 TGas");
+        assert_eq!(format_gas(Gas(5 * TGAS + TGAS / 1000)), "~5.001 TGas");
+    }
+
+    #[test]
+    fn distribute() {
+        let mut budget = GasBudget::new(Gas(100 * TGAS + 2));
+        assert_eq!(budget.reserve("callback", Gas(10 * TGAS)), Gas(10 * TGAS));
+        assert_eq!(budget.remaining(), Gas(90 * TGAS + 2));
+
+        let gas = budget.distribute(&[
+            GasShare::new("a", 1, Gas(TGAS)),
+            GasShare::new("b", 2, Gas(TGAS)),
+        ]);
+        assert_eq!(gas, vec![Gas(30 * TGAS), Gas(60 * TGAS + 2)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Not enough gas for callback: requires 10 TGas, 5 TGas remaining")]
+    fn reserve_too_much() {
+        GasBudget::new(Gas(5 * TGAS)).reserve("callback", Gas(10 * TGAS));
+    }
+
+    #[test]
+    #[should_panic(expected = "Not enough gas for b: requires at least 20 TGas, got 15 TGas")]
+    fn distribute_below_min() {
+        GasBudget::new(Gas(30 * TGAS)).distribute(&[
+            GasShare::new("a", 1, Gas(TGAS)),
+            GasShare::new("b", 1, Gas(20 * TGAS)),
+        ]);
+    }
+}
@@ -7,7 +7,7 @@ use near_sdk::{
         },
         JsonSchema,
     },
-    serde::{Deserialize, Serialize},
+    serde::{de, Deserialize, Deserializer, Serialize, Serializer},
     serde_json::json,
 };
 
@@ -33,8 +33,11 @@ use near_sdk::{
 #[serde(transparent)]
 pub struct JsUint(u64);
 
+/// JS' `Number.MAX_SAFE_INTEGER`, `(2^53) - 1`.
+pub const MAX_SAFE_INTEGER: u64 = (1u64 << 53) - 1u64;
+
 impl JsUint {
-    const MAX: u64 = (1u64 << 53) - 1u64;
+    const MAX: u64 = MAX_SAFE_INTEGER;
 
     pub fn new(n: u64) -> Self {
         assert!(n <= Self::MAX);
@@ -88,6 +91,253 @@ impl JsonSchema for JsUint {
     }
 }
 
+mod private {
+    /// Prevents [`JsInteger`](super::JsInteger) from being implemented
+    /// outside of this module.
+    pub trait Sealed {}
+}
+
+/// Unsigned integer types that can be wrapped by [`JsSafe`].
+///
+/// This trait is sealed, as it's schema relies on [`NUMERIC`](Self::NUMERIC)
+/// and [`MAX`](Self::MAX) being correct.
+pub trait JsInteger: private::Sealed + Copy + Sized + BorshSerialize + BorshDeserialize {
+    /// Name used for the [`JsonSchema`] of `JsSafe<Self>`.
+    const SCHEMA_NAME: &'static str;
+
+    /// `true` if every value of this type fits in a JS `Number`
+    /// (ie. is at most `(2^53) - 1`), in which case it's schema only allows
+    /// JSON numbers.
+    const NUMERIC: bool;
+
+    /// The type's maximum value.
+    const MAX: Self;
+
+    fn to_u128(self) -> u128;
+
+    /// Returns `None` if `n` doesn't fit in `Self`.
+    fn from_u128(n: u128) -> Option<Self>;
+}
+
+macro_rules! impl_js_integer {
+    ($($t:ty => $name:literal, $numeric:literal;)*) => {
+        $(
+            impl private::Sealed for $t {}
+
+            impl JsInteger for $t {
+                const SCHEMA_NAME: &'static str = $name;
+                const NUMERIC: bool = $numeric;
+                const MAX: Self = <$t>::MAX;
+
+                fn to_u128(self) -> u128 {
+                    self as u128
+                }
+
+                fn from_u128(n: u128) -> Option<Self> {
+                    std::convert::TryFrom::try_from(n).ok()
+                }
+            }
+        )*
+    };
+}
+
+impl_js_integer! {
+    u8 => "JsU8", true;
+    u16 => "JsU16", true;
+    u32 => "JsU32", true;
+    u64 => "JsU64", false;
+    u128 => "JsU128", false;
+}
+
+/// An unsigned integer that is de/serialized in a way that is safe for JS.
+///
+/// Values that fit in a JS `Number` (ie. are at most `(2^53) - 1`) are
+/// serialized as JSON numbers, while larger ones are stringfied. So `u8`,
+/// `u16` and `u32` are always numbers, while `u64` and `u128` depend on the
+/// value. For deserialization, both forms are accepted.
+///
+/// For borsh, the structure is considered the inner integer.
+///
+/// See also [`JsUint`] for an `u64` which is restricted to `(2^53) - 1` and
+/// is never stringfied.
+#[derive(
+    Clone, Copy, PartialEq, Eq, Debug, PartialOrd, Ord, Default, BorshDeserialize, BorshSerialize,
+)]
+pub struct JsSafe<T: JsInteger>(pub T);
+
+pub type JsU8 = JsSafe<u8>;
+pub type JsU16 = JsSafe<u16>;
+pub type JsU32 = JsSafe<u32>;
+pub type JsU64 = JsSafe<u64>;
+pub type JsU128 = JsSafe<u128>;
+
+impl<T: JsInteger> JsSafe<T> {
+    pub fn new(n: T) -> Self {
+        Self(n)
+    }
+
+    pub fn get(self) -> T {
+        self.0
+    }
+}
+
+impl<T: JsInteger> From<T> for JsSafe<T> {
+    fn from(n: T) -> Self {
+        Self(n)
+    }
+}
+
+impl<T: JsInteger> Serialize for JsSafe<T> {
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let n = self.0.to_u128();
+        if n <= MAX_SAFE_INTEGER as u128 {
+            serializer.serialize_u64(n as u64)
+        } else {
+            serializer.serialize_str(&n.to_string())
+        }
+    }
+}
+
+impl<'de, T: JsInteger> Deserialize<'de> for JsSafe<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct Visitor<T>(std::marker::PhantomData<T>);
+
+        impl<'de, T: JsInteger> de::Visitor<'de> for Visitor<T> {
+            type Value = JsSafe<T>;
+
+            fn expecting(
+                &self,
+                f: &mut std::fmt::Formatter,
+            ) -> std::fmt::Result {
+                write!(
+                    f,
+                    "an unsigned integer (or it's decimal string) up to {}",
+                    T::MAX.to_u128()
+                )
+            }
+
+            fn visit_u64<E: de::Error>(
+                self,
+                n: u64,
+            ) -> Result<Self::Value, E> {
+                self.visit_u128(n as u128)
+            }
+
+            fn visit_u128<E: de::Error>(
+                self,
+                n: u128,
+            ) -> Result<Self::Value, E> {
+                T::from_u128(n)
+                    .map(JsSafe)
+                    .ok_or_else(|| E::invalid_value(de::Unexpected::Other("integer"), &self))
+            }
+
+            fn visit_i64<E: de::Error>(
+                self,
+                n: i64,
+            ) -> Result<Self::Value, E> {
+                if n < 0 {
+                    return Err(E::invalid_value(de::Unexpected::Signed(n), &self));
+                }
+                self.visit_u128(n as u128)
+            }
+
+            fn visit_str<E: de::Error>(
+                self,
+                s: &str,
+            ) -> Result<Self::Value, E> {
+                if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+                    return Err(E::invalid_value(de::Unexpected::Str(s), &self));
+                }
+                let n: u128 = s
+                    .parse()
+                    .map_err(|_| E::invalid_value(de::Unexpected::Str(s), &self))?;
+                self.visit_u128(n)
+            }
+        }
+
+        deserializer.deserialize_any(Visitor(std::marker::PhantomData))
+    }
+}
+
+impl<T: JsInteger> JsonSchema for JsSafe<T> {
+    fn is_referenceable() -> bool {
+        true
+    }
+    fn schema_name() -> String {
+        T::SCHEMA_NAME.to_owned()
+    }
+    fn json_schema(_gen: &mut SchemaGenerator) -> Schema {
+        let max = T::MAX.to_u128();
+        let max_digits = max.to_string().chars().count() as u32;
+
+        if T::NUMERIC {
+            let n_validation = NumberValidation {
+                maximum: Some(max as f64),
+                minimum: Some(0.0),
+                ..Default::default()
+            };
+
+            let meta = Metadata {
+                description: Some("Unsigned integer.".into()),
+                default: Some(json!(0)),
+                examples: vec![json!(0), json!(max as u64)],
+                ..Default::default()
+            };
+
+            SchemaObject {
+                instance_type: Some(InstanceType::Integer.into()),
+                format: Some(format!("uint{}", std::mem::size_of::<T>() * 8)),
+                metadata: Box::new(meta).into(),
+                number: Some(Box::new(n_validation)),
+                ..Default::default()
+            }
+            .into()
+        } else {
+            // numbers for values that fit in a JS `Number`, strings otherwise
+            let n_validation = NumberValidation {
+                maximum: Some(MAX_SAFE_INTEGER as f64),
+                minimum: Some(0.0),
+                ..Default::default()
+            };
+
+            let s_validation = StringValidation {
+                max_length: Some(max_digits),
+                min_length: Some(1),
+                pattern: Some(format!("^[0-9]{{1,{}}}$", max_digits)),
+            };
+
+            let meta = Metadata {
+                description: Some(
+                    "Unsigned integer, stringfied if it's above `(2^53) - 1`.".into(),
+                ),
+                default: Some(json!(0)),
+                examples: vec![json!(0), json!(max.to_string())],
+                ..Default::default()
+            };
+
+            SchemaObject {
+                instance_type: Some(vec![InstanceType::Integer, InstanceType::String].into()),
+                format: None,
+                metadata: Box::new(meta).into(),
+                number: Some(Box::new(n_validation)),
+                string: Some(Box::new(s_validation)),
+                ..Default::default()
+            }
+            .into()
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -141,4 +391,73 @@ mod tests {
         n.0 -= 1;
         assert_eq!(n.get(), JsUint::MAX);
     }
+
+    #[test]
+    fn js_safe_numeric() {
+        use near_sdk::serde_json;
+
+        let n = JsU32::new(u32::MAX);
+        let json = serde_json::to_string(&n).unwrap();
+        assert_eq!(json, "4294967295");
+        assert_eq!(serde_json::from_str::<JsU32>(&json).unwrap(), n);
+        assert_eq!(serde_json::from_str::<JsU32>(r#""4294967295""#).unwrap(), n);
+
+        assert!(serde_json::from_str::<JsU16>("65536").is_err());
+        assert!(serde_json::from_str::<JsU16>("-1").is_err());
+        assert!(serde_json::from_str::<JsU16>("1.5").is_err());
+    }
+
+    #[test]
+    fn js_safe_stringified() {
+        use near_sdk::serde_json;
+
+        let n = JsU128::new(u128::MAX);
+        let json = serde_json::to_string(&n).unwrap();
+        assert_eq!(json, format!("\"{}\"", u128::MAX));
+        assert_eq!(serde_json::from_str::<JsU128>(&json).unwrap(), n);
+        assert_eq!(serde_json::from_str::<JsU64>("7").unwrap(), JsU64::new(7));
+
+        // numeric up to 2^53 - 1, stringfied above it
+        let safe = JsU64::new(MAX_SAFE_INTEGER);
+        assert_eq!(serde_json::to_string(&safe).unwrap(), "9007199254740991");
+        assert_eq!(serde_json::to_string(&JsU64::new(7)).unwrap(), "7");
+        let unsafe_ = JsU64::new(MAX_SAFE_INTEGER + 1);
+        assert_eq!(
+            serde_json::to_string(&unsafe_).unwrap(),
+            r#""9007199254740992""#
+        );
+        assert_eq!(
+            serde_json::to_string(&JsU128::new(MAX_SAFE_INTEGER as u128)).unwrap(),
+            "9007199254740991"
+        );
+        for n in [safe, unsafe_] {
+            let json = serde_json::to_string(&n).unwrap();
+            assert_eq!(serde_json::from_str::<JsU64>(&json).unwrap(), n);
+        }
+
+        assert!(serde_json::from_str::<JsU64>(r#""18446744073709551616""#).is_err());
+        assert!(serde_json::from_str::<JsU64>(r#""+1""#).is_err());
+        assert!(serde_json::from_str::<JsU64>(r#""""#).is_err());
+    }
+
+    #[test]
+    fn js_safe_schema() {
+        use near_sdk::serde_json;
+
+        let schema = serde_json::to_value(near_sdk::schemars::schema_for!(JsU64)).unwrap();
+        assert_eq!(schema["type"], serde_json::json!(["integer", "string"]));
+        assert_eq!(schema["maximum"], 9007199254740991.0);
+        assert_eq!(schema["pattern"], "^[0-9]{1,20}$");
+
+        let schema = serde_json::to_value(near_sdk::schemars::schema_for!(JsU32)).unwrap();
+        assert_eq!(schema["type"], "integer");
+    }
+
+    #[test]
+    fn js_safe_borsh() {
+        let n = JsU128::new(u128::MAX - 1);
+        let bytes = n.try_to_vec().unwrap();
+        assert_eq!(bytes, (u128::MAX - 1).try_to_vec().unwrap());
+        assert_eq!(JsU128::try_from_slice(&bytes).unwrap(), n);
+    }
 }
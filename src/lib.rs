@@ -1,5 +1,8 @@
 pub mod collections;
+pub mod content_url;
+pub mod gas;
 pub mod js_integer;
+pub mod near_amount;
 pub mod owners;
 pub mod refund;
 pub mod wrapped_url;
@@ -8,10 +11,12 @@ pub mod wrapped_value;
 #[cfg(feature = "sim")]
 pub mod sim;
 
+pub use content_url::{ArweaveUrl, HashedLink, IpfsUrl};
 pub use contract_version as version;
-pub use js_integer::JsUint;
-pub use wrapped_url::Url;
-pub use wrapped_value::Value;
+pub use js_integer::{JsSafe, JsUint};
+pub use near_amount::{near_to_yocto, NearAmount};
+pub use wrapped_url::{CompactUrl, Url, ValidUrl, WebUrl};
+pub use wrapped_value::{BoundedValue, CompactValue, TypedValue, Value};
 
 /// Stringifies some yoctoNEAR amount into it's NEAR representation.
 /// The NEAR unit is not appended into the string.
@@ -0,0 +1,810 @@
+//! Parsing and formatting of human-readable NEAR amounts.
+//!
+//! See [`NearAmount`], [`near_to_yocto`] and [`NearFormat`].
+
+use near_sdk::{
+    borsh::{self, BorshDeserialize, BorshSerialize},
+    schemars::{
+        gen::SchemaGenerator,
+        schema::{InstanceType, Metadata, Schema, SchemaObject, StringValidation},
+        JsonSchema,
+    },
+    serde::{de, Deserialize, Deserializer, Serialize, Serializer},
+    serde_json::json,
+};
+
+/// Amount of yoctoNEAR in one NEAR.
+pub const YOCTO_PER_NEAR: u128 = 10u128.pow(NEAR_DECIMALS);
+
+/// Amount of yoctoNEAR in one milliNEAR.
+pub const YOCTO_PER_MILLINEAR: u128 = 10u128.pow(NEAR_DECIMALS - 3);
+
+/// Number of fractional digits of a NEAR.
+pub const NEAR_DECIMALS: u32 = 24;
+
+/// Units accepted by [`near_to_yocto`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug, PartialOrd, Ord)]
+pub enum NearUnit {
+    /// `yN`.
+    YoctoNear,
+    /// `mN`, `10^21` yN.
+    MilliNear,
+    /// `NEAR` (or `N`), `10^24` yN.
+    Near,
+}
+
+impl NearUnit {
+    /// Parses a unit suffix.
+    ///
+    /// Only the long names are case insensitive, as `mN` and `MN` could
+    /// otherwise be mistaken.
+    pub fn from_suffix(s: &str) -> Option<Self> {
+        match s {
+            "N" => Some(Self::Near),
+            "mN" => Some(Self::MilliNear),
+            "yN" => Some(Self::YoctoNear),
+            _ => match s.to_lowercase().as_str() {
+                "near" => Some(Self::Near),
+                "millinear" => Some(Self::MilliNear),
+                "yocto" | "yoctonear" => Some(Self::YoctoNear),
+                _ => None,
+            },
+        }
+    }
+
+    /// The canonical suffix of the unit.
+    pub fn suffix(self) -> &'static str {
+        match self {
+            Self::Near => "NEAR",
+            Self::MilliNear => "mN",
+            Self::YoctoNear => "yN",
+        }
+    }
+
+    /// How many fractional digits the unit can have without going below
+    /// one yoctoNEAR.
+    pub fn decimals(self) -> u32 {
+        match self {
+            Self::Near => NEAR_DECIMALS,
+            Self::MilliNear => NEAR_DECIMALS - 3,
+            Self::YoctoNear => 0,
+        }
+    }
+
+    /// Amount of yoctoNEAR in one of this unit.
+    pub fn yoctos(self) -> u128 {
+        10u128.pow(self.decimals())
+    }
+}
+
+impl std::fmt::Display for NearUnit {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        f.write_str(self.suffix())
+    }
+}
+
+/// An amount of yoctoNEAR.
+///
+/// It's JSON de/serialization is a stringfied integer of yoctoNEAR, and for
+/// borsh the structure is considered an `u128`. It's [`Display`] is the
+/// amount in NEAR, as given by [`crate::yocto_to_near`] and followed by the
+/// `NEAR` unit, which can be parsed back by it's [`FromStr`].
+///
+/// [`Display`]: std::fmt::Display
+/// [`FromStr`]: std::str::FromStr
+#[derive(
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Debug,
+    PartialOrd,
+    Ord,
+    Default,
+    Hash,
+    BorshDeserialize,
+    BorshSerialize,
+)]
+pub struct NearAmount(u128);
+
+impl NearAmount {
+    pub const ZERO: Self = Self(0);
+    pub const ONE_YOCTO: Self = Self(1);
+    pub const ONE_NEAR: Self = Self(YOCTO_PER_NEAR);
+    pub const MAX: Self = Self(u128::MAX);
+
+    pub const fn from_yoctos(yoctos: u128) -> Self {
+        Self(yoctos)
+    }
+
+    /// Panics if the amount doesn't fit in an `u128` of yoctoNEAR.
+    pub fn from_near(near: u128) -> Self {
+        Self(
+            near.checked_mul(YOCTO_PER_NEAR)
+                .expect("NEAR amount overflow"),
+        )
+    }
+
+    pub const fn as_yoctos(self) -> u128 {
+        self.0
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn checked_add(
+        self,
+        rhs: Self,
+    ) -> Option<Self> {
+        self.0.checked_add(rhs.0).map(Self)
+    }
+
+    pub fn checked_sub(
+        self,
+        rhs: Self,
+    ) -> Option<Self> {
+        self.0.checked_sub(rhs.0).map(Self)
+    }
+
+    pub fn checked_mul(
+        self,
+        rhs: u128,
+    ) -> Option<Self> {
+        self.0.checked_mul(rhs).map(Self)
+    }
+
+    pub fn checked_div(
+        self,
+        rhs: u128,
+    ) -> Option<Self> {
+        self.0.checked_div(rhs).map(Self)
+    }
+
+    pub fn saturating_add(
+        self,
+        rhs: Self,
+    ) -> Self {
+        Self(self.0.saturating_add(rhs.0))
+    }
+
+    pub fn saturating_sub(
+        self,
+        rhs: Self,
+    ) -> Self {
+        Self(self.0.saturating_sub(rhs.0))
+    }
+
+    /// Formats the amount with the given options.
+    pub fn format(
+        self,
+        format: &NearFormat,
+    ) -> String {
+        format.format(self.0)
+    }
+}
+
+impl std::fmt::Display for NearAmount {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        write!(f, "{} {}", crate::yocto_to_near(self.0), NearUnit::Near)
+    }
+}
+
+impl std::str::FromStr for NearAmount {
+    type Err = ParseNearError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        near_to_yocto(s).map(Self)
+    }
+}
+
+impl Serialize for NearAmount {
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        // always stringfied, as in it's schema
+        serializer.serialize_str(&self.0.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for NearAmount {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        // only the stringfied form of it's schema, and not JSON numbers
+        let s = <String as Deserialize>::deserialize(deserializer)?;
+        if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(de::Error::invalid_value(
+                de::Unexpected::Str(&s),
+                &"a decimal yocto string",
+            ));
+        }
+        s.parse()
+            .map(Self)
+            .map_err(|_| de::Error::custom("the yocto amount overflows an u128"))
+    }
+}
+
+impl JsonSchema for NearAmount {
+    fn is_referenceable() -> bool {
+        true
+    }
+    fn schema_name() -> String {
+        ("NearAmount").to_owned()
+    }
+    fn json_schema(_gen: &mut SchemaGenerator) -> Schema {
+        let max_digits = u128::MAX.to_string().chars().count() as u32;
+
+        let s_validation = StringValidation {
+            max_length: Some(max_digits),
+            min_length: Some(1),
+            pattern: Some(format!("^[0-9]{{1,{}}}$", max_digits)),
+        };
+
+        let meta = Metadata {
+            description: Some("Amount of yoctoNEAR (10^-24 NEAR), as a stringfied integer.".into()),
+            default: Some(json!("0")),
+            examples: vec![json!("0"), json!(YOCTO_PER_NEAR.to_string())],
+            ..Default::default()
+        };
+
+        SchemaObject {
+            instance_type: Some(InstanceType::String.into()),
+            format: None,
+            metadata: Box::new(meta).into(),
+            string: Some(Box::new(s_validation)),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+/// Errors from [`near_to_yocto`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ParseNearError {
+    /// The input had no amount.
+    Empty,
+    /// The amount is not a decimal number (eg. `"1.2.3"`, `"-1"` or `"1."`).
+    InvalidNumber(String),
+    /// The suffix is not a known [`NearUnit`].
+    UnknownUnit(String),
+    /// The amount had more fractional digits than the unit allows.
+    ExcessPrecision {
+        unit: NearUnit,
+        max_decimals: u32,
+        decimals: u32,
+    },
+    /// The amount doesn't fit in an `u128` of yoctoNEAR.
+    Overflow,
+}
+
+impl std::fmt::Display for ParseNearError {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        match self {
+            Self::Empty => write!(f, "missing NEAR amount"),
+            Self::InvalidNumber(s) => write!(f, "invalid decimal number: \"{}\"", s),
+            Self::UnknownUnit(s) => write!(
+                f,
+                "unknown unit \"{}\", expected one of \"NEAR\", \"mN\" or \"yN\"",
+                s
+            ),
+            Self::ExcessPrecision {
+                unit,
+                max_decimals,
+                decimals,
+            } => write!(
+                f,
+                "amount in {} has {} fractional digits but at most {} are allowed",
+                unit, decimals, max_decimals
+            ),
+            Self::Overflow => write!(f, "amount exceeds {} yN", u128::MAX),
+        }
+    }
+}
+
+impl std::error::Error for ParseNearError {}
+
+/// Parses an amount of NEAR into yoctoNEAR.
+/// The inverse of [`crate::yocto_to_near`].
+///
+/// The amount is a decimal number optionally followed by a [`NearUnit`]
+/// suffix, which defaults to NEAR. Whitespace around the amount and between
+/// the number and the unit is ignored.
+///
+/// Examples:
+/// - "1" => 1000000000000000000000000 (yN)
+/// - "1.5" => 1500000000000000000000000 (yN)
+/// - "0.000001 NEAR" => 1000000000000000000 (yN)
+/// - "250 mN" => 250000000000000000000000 (yN)
+/// - "3 yN" => 3 (yN)
+pub fn near_to_yocto(s: &str) -> Result<u128, ParseNearError> {
+    let s = s.trim();
+    let number_len = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (number, suffix) = s.split_at(number_len);
+    let suffix = suffix.trim_start();
+
+    if number.is_empty() {
+        return if s.is_empty() {
+            Err(ParseNearError::Empty)
+        } else {
+            Err(ParseNearError::InvalidNumber(s.to_string()))
+        };
+    }
+
+    let unit = if suffix.is_empty() {
+        NearUnit::Near
+    } else {
+        NearUnit::from_suffix(suffix)
+            .ok_or_else(|| ParseNearError::UnknownUnit(suffix.to_string()))?
+    };
+
+    let (int, frac) = match number.split_once('.') {
+        Some((int, frac)) => (int, frac),
+        None => (number, ""),
+    };
+    let invalid = || ParseNearError::InvalidNumber(number.to_string());
+    if int.is_empty() || frac.contains('.') || (number.contains('.') && frac.is_empty()) {
+        return Err(invalid());
+    }
+
+    // trailing zeros don't add precision
+    let frac = frac.trim_end_matches('0');
+    let decimals = frac.len() as u32;
+    if decimals > unit.decimals() {
+        return Err(ParseNearError::ExcessPrecision {
+            unit,
+            max_decimals: unit.decimals(),
+            decimals,
+        });
+    }
+
+    let int: u128 = parse_digits(int)?;
+    let frac: u128 = if frac.is_empty() {
+        0
+    } else {
+        parse_digits(frac)? * 10u128.pow(unit.decimals() - decimals)
+    };
+
+    int.checked_mul(unit.yoctos())
+        .and_then(|int| int.checked_add(frac))
+        .ok_or(ParseNearError::Overflow)
+}
+
+/// Parses a non-empty sequence of ascii digits.
+fn parse_digits(digits: &str) -> Result<u128, ParseNearError> {
+    digits.bytes().try_fold(0u128, |acc, b| {
+        acc.checked_mul(10)
+            .and_then(|acc| acc.checked_add((b - b'0') as u128))
+            .ok_or(ParseNearError::Overflow)
+    })
+}
+
+/// How to round the digits that are dropped when formatting with
+/// [`NearFormat::round`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Rounding {
+    /// Towards zero (truncation).
+    Down,
+    /// Away from zero.
+    Up,
+    /// To the nearest, ties away from zero.
+    HalfUp,
+    /// To the nearest, ties to the even digit.
+    HalfEven,
+}
+
+/// Which unit a [`NearFormat`] writes the amount in.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum UnitChoice {
+    /// Always uses the given unit.
+    Fixed(NearUnit),
+    /// Uses the largest unit in which the amount is at least one.
+    Adaptive,
+}
+
+/// Configurable formatting of yoctoNEAR amounts.
+///
+/// The default configuration writes the amount in NEAR with all of it's
+/// 24 fractional digits and without a unit suffix, the same as
+/// [`crate::yocto_to_near`].
+///
+/// Examples:
+/// ```
+/// use common::near_amount::{NearFormat, Rounding};
+///
+/// let yoctos = 1_234_500_000_000_000_000_000_000_000;
+/// let f = NearFormat::new().trim_zeros().thousands(',').suffix();
+/// assert_eq!(f.format(yoctos), "1,234.5 NEAR");
+///
+/// let f = NearFormat::new().round(2, Rounding::HalfEven).adaptive();
+/// assert_eq!(f.format(yoctos), "1234.50 NEAR");
+/// assert_eq!(f.format(250_000_000_000_000_000_000_000), "250.00 mN");
+/// assert_eq!(f.format(3), "3 yN");
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct NearFormat {
+    pub unit: UnitChoice,
+    /// Whether the unit suffix is appended.
+    pub suffix: bool,
+    /// Maximum amount of fractional digits, and how to round the dropped
+    /// ones.
+    pub round: Option<(u32, Rounding)>,
+    /// Whether trailing fractional zeros (and a trailing `.`) are removed.
+    pub trim_zeros: bool,
+    /// Separator inserted between each group of three integer digits.
+    pub thousands: Option<char>,
+}
+
+impl Default for NearFormat {
+    fn default() -> Self {
+        Self {
+            unit: UnitChoice::Fixed(NearUnit::Near),
+            suffix: false,
+            round: None,
+            trim_zeros: false,
+            thousands: None,
+        }
+    }
+}
+
+impl NearFormat {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writes the amount in the given unit.
+    pub fn unit(
+        mut self,
+        unit: NearUnit,
+    ) -> Self {
+        self.unit = UnitChoice::Fixed(unit);
+        self
+    }
+
+    /// Writes the amount in the largest unit in which it's at least one,
+    /// and appends the unit suffix.
+    pub fn adaptive(mut self) -> Self {
+        self.unit = UnitChoice::Adaptive;
+        self.suffix = true;
+        self
+    }
+
+    /// Appends the unit suffix.
+    pub fn suffix(mut self) -> Self {
+        self.suffix = true;
+        self
+    }
+
+    /// Keeps at most `decimals` fractional digits.
+    pub fn round(
+        mut self,
+        decimals: u32,
+        rounding: Rounding,
+    ) -> Self {
+        self.round = Some((decimals, rounding));
+        self
+    }
+
+    /// Removes trailing fractional zeros.
+    pub fn trim_zeros(mut self) -> Self {
+        self.trim_zeros = true;
+        self
+    }
+
+    /// Separates the groups of integer digits with `separator`.
+    pub fn thousands(
+        mut self,
+        separator: char,
+    ) -> Self {
+        self.thousands = Some(separator);
+        self
+    }
+
+    /// Formats the amount of yoctoNEAR.
+    pub fn format(
+        &self,
+        yoctos: u128,
+    ) -> String {
+        let mut unit = match self.unit {
+            UnitChoice::Fixed(unit) => unit,
+            UnitChoice::Adaptive => adaptive_unit(yoctos),
+        };
+
+        let (mut int, mut frac, mut decimals) = self.split(yoctos, unit);
+        if self.unit == UnitChoice::Adaptive && unit == NearUnit::MilliNear && int >= 1000 {
+            // rounding reached the next unit (eg. 999.9996 mN => 1.000 NEAR)
+            unit = NearUnit::Near;
+            (int, frac, decimals) = self.split(yoctos, unit);
+        }
+
+        let mut frac = if decimals == 0 {
+            String::new()
+        } else {
+            format!("{:0>width$}", frac, width = decimals as usize)
+        };
+        if self.trim_zeros {
+            frac.truncate(frac.trim_end_matches('0').len());
+        }
+
+        let mut s = match self.thousands {
+            Some(separator) => group_thousands(&int.to_string(), separator),
+            None => int.to_string(),
+        };
+        if !frac.is_empty() {
+            s.push('.');
+            s.push_str(&frac);
+        }
+        if self.suffix {
+            s.push(' ');
+            s.push_str(unit.suffix());
+        }
+        s
+    }
+
+    /// Splits the amount into it's rounded integer part, fractional part
+    /// and amount of fractional digits, in the given unit.
+    fn split(
+        &self,
+        yoctos: u128,
+        unit: NearUnit,
+    ) -> (u128, u128, u32) {
+        let mut int = yoctos / unit.yoctos();
+        let frac = yoctos % unit.yoctos();
+
+        let (decimals, rounding) = match self.round {
+            Some((decimals, rounding)) if decimals < unit.decimals() => (decimals, rounding),
+            _ => return (int, frac, unit.decimals()),
+        };
+
+        let scale = 10u128.pow(unit.decimals() - decimals);
+        let half = scale / 2;
+        let (mut frac, rem) = (frac / scale, frac % scale);
+        let increment = match rounding {
+            Rounding::Down => false,
+            Rounding::Up => rem > 0,
+            Rounding::HalfUp => rem >= half,
+            Rounding::HalfEven => {
+                // without decimals, the last kept digit is the integer's
+                let last = if decimals == 0 { int } else { frac };
+                rem > half || (rem == half && last % 2 == 1)
+            }
+        };
+        if increment {
+            frac += 1;
+            if frac == 10u128.pow(decimals) {
+                frac = 0;
+                int += 1;
+            }
+        }
+        (int, frac, decimals)
+    }
+}
+
+/// The largest unit in which `yoctos` is at least one.
+fn adaptive_unit(yoctos: u128) -> NearUnit {
+    if yoctos >= NearUnit::Near.yoctos() {
+        NearUnit::Near
+    } else if yoctos >= NearUnit::MilliNear.yoctos() {
+        NearUnit::MilliNear
+    } else {
+        NearUnit::YoctoNear
+    }
+}
+
+fn group_thousands(
+    digits: &str,
+    separator: char,
+) -> String {
+    let mut s = String::with_capacity(digits.len() * 4 / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            s.push(separator);
+        }
+        s.push(c);
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_units() {
+        assert_eq!(near_to_yocto("1"), Ok(YOCTO_PER_NEAR));
+        assert_eq!(near_to_yocto("1.5"), Ok(15 * YOCTO_PER_NEAR / 10));
+        assert_eq!(near_to_yocto("0.000001 NEAR"), Ok(10u128.pow(18)));
+        assert_eq!(near_to_yocto(" 2near "), Ok(2 * YOCTO_PER_NEAR));
+        assert_eq!(near_to_yocto("250 mN"), Ok(250 * YOCTO_PER_MILLINEAR));
+        assert_eq!(near_to_yocto("3 yN"), Ok(3));
+        assert_eq!(near_to_yocto("3.000 yN"), Ok(3));
+        assert_eq!(near_to_yocto("0.000000000000000000000001"), Ok(1));
+    }
+
+    #[test]
+    fn roundtrip() {
+        for yoctos in [0, 1, YOCTO_PER_NEAR, 1010000000000000000000000, u128::MAX] {
+            assert_eq!(near_to_yocto(&crate::yocto_to_near(yoctos)), Ok(yoctos));
+        }
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(near_to_yocto("  "), Err(ParseNearError::Empty));
+        assert_eq!(
+            near_to_yocto("1.2.3"),
+            Err(ParseNearError::InvalidNumber("1.2.3".into()))
+        );
+        assert_eq!(
+            near_to_yocto("-1"),
+            Err(ParseNearError::InvalidNumber("-1".into()))
+        );
+        assert_eq!(
+            near_to_yocto("1."),
+            Err(ParseNearError::InvalidNumber("1.".into()))
+        );
+        assert_eq!(
+            near_to_yocto(".5"),
+            Err(ParseNearError::InvalidNumber(".5".into()))
+        );
+        assert_eq!(
+            near_to_yocto("1 MN"),
+            Err(ParseNearError::UnknownUnit("MN".into()))
+        );
+        assert_eq!(
+            near_to_yocto("1.5 yN"),
+            Err(ParseNearError::ExcessPrecision {
+                unit: NearUnit::YoctoNear,
+                max_decimals: 0,
+                decimals: 1
+            })
+        );
+        assert_eq!(
+            near_to_yocto("0.0000000000000000000000001"),
+            Err(ParseNearError::ExcessPrecision {
+                unit: NearUnit::Near,
+                max_decimals: 24,
+                decimals: 25
+            })
+        );
+        assert_eq!(
+            near_to_yocto("340282366920938463463375 NEAR"),
+            Err(ParseNearError::Overflow)
+        );
+        assert_eq!(
+            near_to_yocto(&format!("{}0 yN", u128::MAX)),
+            Err(ParseNearError::Overflow)
+        );
+    }
+
+    #[test]
+    fn format_default() {
+        for yoctos in [0, 1, YOCTO_PER_NEAR, 1010000000000000000000000, u128::MAX] {
+            assert_eq!(
+                NearFormat::new().format(yoctos),
+                crate::yocto_to_near(yoctos)
+            );
+        }
+    }
+
+    #[test]
+    fn format_rounding() {
+        let f = |decimals, rounding| {
+            let f = NearFormat::new().round(decimals, rounding);
+            move |s: &str| f.format(near_to_yocto(s).unwrap())
+        };
+
+        let down = f(1, Rounding::Down);
+        assert_eq!(down("1.25"), "1.2");
+        assert_eq!(down("0.99"), "0.9");
+
+        let up = f(1, Rounding::Up);
+        assert_eq!(up("1.21"), "1.3");
+        assert_eq!(up("1.2"), "1.2");
+        assert_eq!(up("0.99"), "1.0");
+
+        let half_up = f(1, Rounding::HalfUp);
+        assert_eq!(half_up("1.25"), "1.3");
+        assert_eq!(half_up("1.249"), "1.2");
+
+        let half_even = f(1, Rounding::HalfEven);
+        assert_eq!(half_even("1.25"), "1.2");
+        assert_eq!(half_even("1.35"), "1.4");
+        assert_eq!(half_even("1.2500001"), "1.3");
+
+        assert_eq!(f(0, Rounding::HalfUp)("1.5"), "2");
+
+        let half_even = f(0, Rounding::HalfEven);
+        for (near, expected) in [("0.5", "0"), ("1.5", "2"), ("2.5", "2"), ("3.5", "4")] {
+            assert_eq!(half_even(near), expected, "{}", near);
+        }
+        assert_eq!(
+            f(30, Rounding::HalfUp)("1.5"),
+            crate::yocto_to_near(near_to_yocto("1.5").unwrap())
+        );
+    }
+
+    #[test]
+    fn format_options() {
+        let f = NearFormat::new().trim_zeros();
+        assert_eq!(f.format(YOCTO_PER_NEAR), "1");
+        assert_eq!(f.format(1010000000000000000000000), "1.01");
+        assert_eq!(f.format(0), "0");
+
+        let f = NearFormat::new().trim_zeros().thousands('_');
+        assert_eq!(f.format(1_234_567 * YOCTO_PER_NEAR), "1_234_567");
+        assert_eq!(f.format(123_456 * YOCTO_PER_NEAR), "123_456");
+
+        let f = NearFormat::new()
+            .unit(NearUnit::YoctoNear)
+            .thousands(',')
+            .suffix();
+        assert_eq!(f.format(1_000), "1,000 yN");
+
+        let f = NearFormat::new().adaptive().trim_zeros();
+        assert_eq!(f.format(0), "0 yN");
+        assert_eq!(f.format(999), "999 yN");
+        assert_eq!(f.format(YOCTO_PER_MILLINEAR), "1 mN");
+        assert_eq!(f.format(YOCTO_PER_NEAR + YOCTO_PER_MILLINEAR), "1.001 NEAR");
+
+        let f = NearFormat::new().adaptive().round(2, Rounding::HalfUp);
+        assert_eq!(f.format(YOCTO_PER_NEAR - 1), "1.00 NEAR");
+        assert_eq!(
+            f.format(YOCTO_PER_MILLINEAR - 1),
+            "999999999999999999999 yN"
+        );
+    }
+
+    #[test]
+    fn near_amount() {
+        use near_sdk::serde_json;
+
+        let amount = NearAmount::from_near(1)
+            .checked_add(NearAmount::ONE_YOCTO)
+            .unwrap();
+        assert_eq!(amount.to_string(), "1.000000000000000000000001 NEAR");
+        assert_eq!(amount.to_string().parse(), Ok(amount));
+
+        let json = serde_json::to_string(&amount).unwrap();
+        assert_eq!(json, r#""1000000000000000000000001""#);
+        assert_eq!(serde_json::from_str::<NearAmount>(&json).unwrap(), amount);
+        // stringfied even when it fits in a JS `Number`
+        assert_eq!(
+            serde_json::to_string(&NearAmount::ONE_YOCTO).unwrap(),
+            r#""1""#
+        );
+        assert!(serde_json::from_str::<NearAmount>("1").is_err());
+        assert!(serde_json::from_str::<NearAmount>(r#""1.5""#).is_err());
+        assert!(serde_json::from_str::<NearAmount>(r#""""#).is_err());
+        let overflow = format!(r#""{}0""#, u128::MAX);
+        assert!(serde_json::from_str::<NearAmount>(&overflow).is_err());
+
+        let bytes = amount.try_to_vec().unwrap();
+        assert_eq!(bytes, amount.as_yoctos().try_to_vec().unwrap());
+        assert_eq!(NearAmount::try_from_slice(&bytes).unwrap(), amount);
+
+        assert_eq!(NearAmount::MAX.checked_add(NearAmount::ONE_YOCTO), None);
+        assert_eq!(NearAmount::ZERO.checked_sub(NearAmount::ONE_YOCTO), None);
+        assert_eq!(NearAmount::ONE_NEAR.checked_div(0), None);
+        assert_eq!(
+            NearAmount::ZERO.saturating_sub(NearAmount::ONE_NEAR),
+            NearAmount::ZERO
+        );
+    }
+}
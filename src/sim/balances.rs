@@ -0,0 +1,500 @@
+//! Tracks how the balances of accounts change while a test runs some
+//! transactions, separating the gas burnt, the deposits and the refunds that
+//! the accounts received (see [`track_balances`]).
+
+use super::batch::BatchExecution;
+use super::contract_ext::Execution;
+use super::costs::RuntimeCosts;
+use super::execution_ext::pretty_near;
+use super::receipt_tree::ReceiptNode;
+use near_sdk::{AccountId, Balance};
+use near_sdk_sim::{ExecutionResult, UserAccount};
+
+/// Accounts which balances can be tracked.
+pub trait AsUserAccount {
+    fn as_user_account(&self) -> &UserAccount;
+}
+
+impl AsUserAccount for UserAccount {
+    fn as_user_account(&self) -> &UserAccount {
+        self
+    }
+}
+
+impl<T> AsUserAccount for near_sdk_sim::ContractAccount<T> {
+    fn as_user_account(&self) -> &UserAccount {
+        &self.user_account
+    }
+}
+
+/// Results that contain the transactions that were executed, so the gas
+/// that they burnt and the deposits and refunds that they made can be
+/// accounted for.
+pub trait Executions {
+    /// The trees of the executed transactions.
+    fn receipt_trees(&self) -> Vec<ReceiptNode>;
+}
+
+impl Executions for () {
+    fn receipt_trees(&self) -> Vec<ReceiptNode> {
+        vec![]
+    }
+}
+
+/// The result must be of the last transaction that was submitted.
+///
+/// It's receipts aren't known, so it has no deposits nor refunds.
+impl Executions for ExecutionResult {
+    fn receipt_trees(&self) -> Vec<ReceiptNode> {
+        // the outcomes since the last transaction, starting with the
+        // transaction's own
+        self.promise_results()
+            .into_iter()
+            .next()
+            .flatten()
+            .map(|transaction| ReceiptNode::new(&transaction))
+            .into_iter()
+            .collect()
+    }
+}
+
+impl<T> Executions for Execution<T> {
+    fn receipt_trees(&self) -> Vec<ReceiptNode> {
+        vec![self.receipt_tree()]
+    }
+}
+
+impl<T> Executions for BatchExecution<T> {
+    fn receipt_trees(&self) -> Vec<ReceiptNode> {
+        vec![self.receipt_tree()]
+    }
+}
+
+impl<T: Executions> Executions for Vec<T> {
+    fn receipt_trees(&self) -> Vec<ReceiptNode> {
+        self.iter().flat_map(Executions::receipt_trees).collect()
+    }
+}
+
+impl<A: Executions, B: Executions> Executions for (A, B) {
+    fn receipt_trees(&self) -> Vec<ReceiptNode> {
+        let mut trees = self.0.receipt_trees();
+        trees.extend(self.1.receipt_trees());
+        trees
+    }
+}
+
+/// Balances of an account at some point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Balances {
+    pub amount: Balance,
+    pub locked: Balance,
+    pub storage_usage: u64,
+}
+
+impl Balances {
+    /// The balances of the `account`, which are all zero if it doesn't
+    /// exist, such as before it's created or after it's deleted.
+    pub fn of(account: &impl AsUserAccount) -> Self {
+        account
+            .as_user_account()
+            .account()
+            .map_or_else(Self::default, |account| Self {
+                amount: account.amount,
+                locked: account.locked,
+                storage_usage: account.storage_usage,
+            })
+    }
+}
+
+/// How the balances of an account changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BalanceDiff {
+    pub account_id: AccountId,
+    pub before: Balances,
+    pub after: Balances,
+    /// Tokens burnt for the gas of the transactions that the account signed,
+    /// including the receipts that they created.
+    /// Prepaid gas that was not burnt is refunded and is not counted.
+    pub gas_burnt: Balance,
+    /// Balance that must be kept per byte of storage.
+    pub storage_price_per_byte: Balance,
+    deposits_received: Balance,
+    refunds_received: Balance,
+}
+
+impl BalanceDiff {
+    /// Change of the liquid balance.
+    pub fn liquid(&self) -> i128 {
+        self.after.amount as i128 - self.before.amount as i128
+    }
+
+    /// Change of the locked balance.
+    pub fn locked(&self) -> i128 {
+        self.after.locked as i128 - self.before.locked as i128
+    }
+
+    /// Change of the storage usage, in bytes.
+    pub fn storage_usage(&self) -> i64 {
+        self.after.storage_usage as i64 - self.before.storage_usage as i64
+    }
+
+    /// Change of the balance that is staked for storage.
+    pub fn storage_staked(&self) -> i128 {
+        self.storage_usage() as i128 * self.storage_price_per_byte as i128
+    }
+
+    /// Tokens that were received (if positive) or sent (if negative),
+    /// besides the gas burnt.
+    ///
+    /// This includes the [deposits](BalanceDiff::deposits_received) and
+    /// [refunds](BalanceDiff::refunds_received) received, less the deposits
+    /// sent, and for contracts, their share of the gas burnt by their
+    /// methods.
+    ///
+    /// The balance staked for storage doesn't leave the account, so it's not
+    /// included (see [`storage_staked`](BalanceDiff::storage_staked)).
+    pub fn received(&self) -> i128 {
+        self.liquid() + self.gas_burnt as i128
+    }
+
+    /// Deposits attached to the receipts that the account executed
+    /// successfully, such as transfers and function calls, besides refunds.
+    ///
+    /// Receipts are only known if they were kept by the
+    /// [`runtime`](super::runtime), and refunds can only be told apart if
+    /// the simulator was created by it.
+    pub fn deposits_received(&self) -> Balance {
+        self.deposits_received
+    }
+
+    /// Deposits and prepaid gas that were refunded to the account.
+    pub fn refunds_received(&self) -> Balance {
+        self.refunds_received
+    }
+
+    /// Liquid balance that is not required for storage, after the change.
+    pub fn free(&self) -> i128 {
+        self.liquid() - self.storage_staked()
+    }
+}
+
+impl std::fmt::Display for BalanceDiff {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: liquid {} (received {}, of which deposits {} and refunds {}, gas burnt {}), locked {}, storage {} bytes ({})",
+            self.account_id,
+            pretty_signed_near(self.liquid()),
+            pretty_signed_near(self.received()),
+            pretty_near(self.deposits_received),
+            pretty_near(self.refunds_received),
+            pretty_near(self.gas_burnt),
+            pretty_signed_near(self.locked()),
+            self.storage_usage(),
+            pretty_signed_near(self.storage_staked()),
+        )
+    }
+}
+
+fn pretty_signed_near(near: i128) -> String {
+    let sign = if near < 0 { "-" } else { "" };
+    format!("{}{}", sign, pretty_near(near.unsigned_abs()))
+}
+
+/// Balance changes of a set of accounts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BalanceDiffs {
+    pub diffs: Vec<BalanceDiff>,
+}
+
+impl BalanceDiffs {
+    /// Gets the diff of a tracked account.
+    pub fn get(
+        &self,
+        account: &impl AsUserAccount,
+    ) -> &BalanceDiff {
+        let account_id = account.as_user_account().account_id();
+        self.diffs
+            .iter()
+            .find(|diff| diff.account_id == account_id)
+            .unwrap_or_else(|| panic!("the balance of {} was not tracked", account_id))
+    }
+
+    /// Asserts that the account received `amount` (or sent it, if negative),
+    /// besides the gas burnt, within some `tolerance`.
+    pub fn assert_received(
+        &self,
+        account: &impl AsUserAccount,
+        amount: i128,
+        tolerance: Balance,
+    ) {
+        let diff = self.get(account);
+        assert!(
+            diff.received().abs_diff(amount) <= tolerance,
+            "expected {} to receive {} (± {}), but {}",
+            diff.account_id,
+            pretty_signed_near(amount),
+            pretty_near(tolerance),
+            diff
+        );
+    }
+
+    /// Asserts that the account burnt `amount` of gas tokens, within some
+    /// `tolerance`.
+    pub fn assert_gas_burnt(
+        &self,
+        account: &impl AsUserAccount,
+        amount: Balance,
+        tolerance: Balance,
+    ) {
+        let diff = self.get(account);
+        assert!(
+            diff.gas_burnt.abs_diff(amount) <= tolerance,
+            "expected {} to burn {} (± {}) of gas, but {}",
+            diff.account_id,
+            pretty_near(amount),
+            pretty_near(tolerance),
+            diff
+        );
+    }
+
+    /// Asserts that the account received deposits of exactly `amount`,
+    /// besides refunds.
+    pub fn assert_deposits_received(
+        &self,
+        account: &impl AsUserAccount,
+        amount: Balance,
+    ) {
+        let diff = self.get(account);
+        assert_eq!(
+            diff.deposits_received(),
+            amount,
+            "unexpected deposits received: {}",
+            diff
+        );
+    }
+
+    /// Asserts that the account received refunds of `amount`, within some
+    /// `tolerance`.
+    pub fn assert_refunds_received(
+        &self,
+        account: &impl AsUserAccount,
+        amount: Balance,
+        tolerance: Balance,
+    ) {
+        let diff = self.get(account);
+        assert!(
+            diff.refunds_received().abs_diff(amount) <= tolerance,
+            "expected {} to be refunded {} (± {}), but {}",
+            diff.account_id,
+            pretty_near(amount),
+            pretty_near(tolerance),
+            diff
+        );
+    }
+
+    /// Asserts that the account's storage usage changed by `bytes`.
+    pub fn assert_storage_usage(
+        &self,
+        account: &impl AsUserAccount,
+        bytes: i64,
+    ) {
+        let diff = self.get(account);
+        assert_eq!(
+            diff.storage_usage(),
+            bytes,
+            "unexpected storage usage change: {}",
+            diff
+        );
+    }
+
+    /// Asserts that none of the account's balances changed.
+    pub fn assert_unchanged(
+        &self,
+        account: &impl AsUserAccount,
+    ) {
+        let diff = self.get(account);
+        assert_eq!(diff.before, diff.after, "unexpected change: {}", diff);
+    }
+}
+
+impl std::fmt::Display for BalanceDiffs {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        for diff in &self.diffs {
+            writeln!(f, "{}", diff)?;
+        }
+        Ok(())
+    }
+}
+
+/// Snapshots the balances of the `accounts`, runs `f` and returns it's
+/// result together with how the balances changed.
+///
+/// The gas burnt, deposits and refunds are taken from the executions that
+/// `f` returns.
+pub fn track_balances<R: Executions>(
+    accounts: &[&dyn AsUserAccount],
+    f: impl FnOnce() -> R,
+) -> (R, BalanceDiffs) {
+    let before: Vec<Balances> = accounts
+        .iter()
+        .map(|account| Balances::of(account.as_user_account()))
+        .collect();
+
+    let res = f();
+
+    let trees = res.receipt_trees();
+    let diffs = accounts
+        .iter()
+        .zip(before)
+        .map(|(account, before)| {
+            let user = account.as_user_account();
+            let account_id = user.account_id();
+            // transactions are executed by their signers
+            let gas_burnt = trees
+                .iter()
+                .filter(|tree| tree.executor_id == account_id)
+                .map(ReceiptNode::total_tokens_burnt)
+                .sum();
+            let received = trees
+                .iter()
+                .flat_map(|tree| tree.find_all(|node| node.executor_id == account_id))
+                .filter(|node| node.is_success());
+            let (refunds, deposits): (Vec<&ReceiptNode>, Vec<&ReceiptNode>) =
+                received.partition(|node| node.is_refund());
+            BalanceDiff {
+                account_id,
+                before,
+                after: Balances::of(user),
+                gas_burnt,
+                storage_price_per_byte: RuntimeCosts::new(user).storage_price_per_byte,
+                deposits_received: deposits.into_iter().map(ReceiptNode::deposit).sum(),
+                refunds_received: refunds.into_iter().map(ReceiptNode::deposit).sum(),
+            }
+        })
+        .collect();
+    (res, BalanceDiffs { diffs })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gas::TGAS;
+    use crate::sim::contract_ext::{deploy_test_contract, ContractExt};
+    use crate::sim::{runtime, BatchExt};
+    use near_sdk::serde_json::json;
+    use near_sdk_sim::{init_simulator, to_yocto};
+
+    #[test]
+    fn transfer() {
+        let root = runtime::init_simulator(None);
+        let costs = RuntimeCosts::new(&root);
+        let alice = root.create_user("alice".parse().unwrap(), to_yocto("10"));
+        let bob = root.create_user("bob".parse().unwrap(), to_yocto("10"));
+        let carol = root.create_user("carol".parse().unwrap(), to_yocto("10"));
+
+        let (res, diffs) = track_balances(&[&alice, &bob, &carol], || {
+            alice
+                .batch(bob.account_id())
+                .transfer(to_yocto("1"))
+                .submit::<()>()
+        });
+        res.assert_success();
+
+        diffs.assert_received(&alice, -(to_yocto("1") as i128), 0);
+        diffs.assert_gas_burnt(&alice, costs.transfer_cost() - costs.transfer_refund(), 0);
+        diffs.assert_deposits_received(&alice, 0);
+        diffs.assert_refunds_received(&alice, costs.transfer_refund(), 0);
+        diffs.assert_received(&bob, to_yocto("1") as i128, 0);
+        diffs.assert_deposits_received(&bob, to_yocto("1"));
+        diffs.assert_refunds_received(&bob, 0, 0);
+        diffs.assert_gas_burnt(&bob, 0, 0);
+        diffs.assert_storage_usage(&bob, 0);
+        diffs.assert_unchanged(&carol);
+    }
+
+    #[test]
+    fn refunded_deposit() {
+        let root = runtime::init_simulator(None);
+        let costs = RuntimeCosts::new(&root);
+        let alice = root.create_user("alice".parse().unwrap(), to_yocto("10"));
+
+        let (res, diffs) = track_balances(&[&alice], || {
+            alice
+                .batch("nobody".parse().unwrap())
+                .transfer(to_yocto("1"))
+                .submit::<()>()
+        });
+        assert_eq!(res.receipt_tree().failures().len(), 1);
+
+        // the deposit comes back, and only the gas is spent
+        diffs.assert_received(&alice, 0, 0);
+        diffs.assert_deposits_received(&alice, 0);
+        diffs.assert_refunds_received(&alice, to_yocto("1") + costs.transfer_refund(), 0);
+    }
+
+    #[test]
+    fn storage_staking() {
+        let root = runtime::init_simulator(None);
+        let alice = root.create_user("alice".parse().unwrap(), to_yocto("10"));
+        let contract = deploy_test_contract(&root, "contract");
+        let deposit = to_yocto("0.01");
+
+        let (res, diffs) = track_balances(&[&alice, &contract], || {
+            contract.json_call::<()>(&alice, "write", json!("hello"), (10 * TGAS).into(), deposit)
+        });
+        res.assert_success();
+
+        let diff = diffs.get(&contract);
+        assert!(diff.storage_usage() > 0);
+        diffs.assert_deposits_received(&contract, deposit);
+        diffs.assert_refunds_received(&contract, 0, 0);
+        // the staked balance stays in the account, so the contract only
+        // received the deposit and it's share of the gas burnt
+        let reward = diff.received() - deposit as i128;
+        assert!(reward > 0);
+        assert!(reward < diff.storage_staked());
+
+        diffs.assert_received(&alice, -(deposit as i128), 0);
+        assert!(diffs.get(&alice).refunds_received() > 0);
+    }
+
+    #[test]
+    fn deleted_account() {
+        let root = init_simulator(None);
+        let alice = root.create_user("alice".parse().unwrap(), to_yocto("10"));
+
+        let (res, diffs) = track_balances(&[&alice], || {
+            alice
+                .create_transaction(alice.account_id())
+                .delete_account(root.account_id())
+                .submit()
+        });
+        res.assert_success();
+
+        let diff = diffs.get(&alice);
+        assert_eq!(diff.after, Balances::default());
+        assert_eq!(diff.liquid(), -(to_yocto("10") as i128));
+        assert!(diff.storage_usage() < 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "expected bob to receive")]
+    fn received_mismatch() {
+        let root = init_simulator(None);
+        let alice = root.create_user("alice".parse().unwrap(), to_yocto("10"));
+        let bob = root.create_user("bob".parse().unwrap(), to_yocto("10"));
+
+        let (_res, diffs) = track_balances(&[&alice, &bob], || {
+            alice.transfer(bob.account_id(), to_yocto("1"))
+        });
+        diffs.assert_received(&bob, to_yocto("2") as i128, to_yocto("0.5"));
+    }
+}
@@ -0,0 +1,700 @@
+//! Transactions with multiple actions, which are executed atomically in a
+//! single receipt.
+//!
+//! ```ignore
+//! let execution = root
+//!     .batch(contract_id)
+//!     .create_account()
+//!     .transfer(to_yocto("10"))
+//!     .deploy(&WASM_BYTES)
+//!     .function_call("new", json!({}), Gas(10 * TGAS), 0)
+//!     .submit::<()>();
+//! execution.assert_action_success(3);
+//! ```
+
+use super::contract_ext::Execution;
+use super::costs::RuntimeCosts;
+use super::failure::FailureKind;
+use super::storage::{self, StorageRecord};
+use super::transcript::{self, TranscriptEntry};
+use super::{gas_profile, runtime};
+use near_sdk::serde::de::DeserializeOwned;
+use near_sdk::{serde_json, AccountId, Balance, Gas};
+use near_sdk_sim::account::AccessKey;
+use near_sdk_sim::errors::{ActionError, TxExecutionError};
+use near_sdk_sim::near_crypto::PublicKey;
+use near_sdk_sim::transaction::{ExecutionStatus, Transaction};
+use near_sdk_sim::UserAccount;
+
+/// An action of a [`Batch`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum BatchAction {
+    CreateAccount,
+    Transfer(Balance),
+    DeployContract {
+        code_len: usize,
+    },
+    AddKey {
+        public_key: PublicKey,
+    },
+    FunctionCall {
+        method: String,
+        args: serde_json::Value,
+        gas: Gas,
+        deposit: Balance,
+    },
+}
+
+/// What happened to an action of a [`Batch`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ActionOutcome {
+    Success,
+    Failure(TxExecutionError),
+    /// The transaction was invalid, or it's receipt failed before executing
+    /// any action, so no action was executed.
+    ///
+    /// Invalid transactions, such as when the signer can't pay for them, are
+    /// only seen on runtimes created by
+    /// [`runtime::init_simulator`](super::runtime::init_simulator), as the
+    /// simulator otherwise panics.
+    Invalid(TxExecutionError),
+    /// A previous action failed.
+    NotExecuted,
+}
+
+/// Builds a transaction with multiple actions, from the signer to the
+/// receiver.
+pub struct Batch<'signer> {
+    signer: &'signer UserAccount,
+    receiver_id: AccountId,
+    tx: Transaction,
+    actions: Vec<BatchAction>,
+}
+
+impl<'signer> Batch<'signer> {
+    pub fn new(
+        signer: &'signer UserAccount,
+        receiver_id: AccountId,
+    ) -> Self {
+        Self {
+            signer,
+            tx: runtime::transaction(signer, &receiver_id),
+            receiver_id,
+            actions: vec![],
+        }
+    }
+
+    fn push(
+        mut self,
+        action: BatchAction,
+        add: impl FnOnce(Transaction) -> Transaction,
+    ) -> Self {
+        self.tx = add(self.tx);
+        self.actions.push(action);
+        self
+    }
+
+    /// Creates the receiver account.
+    pub fn create_account(self) -> Self {
+        self.push(BatchAction::CreateAccount, |tx| tx.create_account())
+    }
+
+    pub fn transfer(
+        self,
+        deposit: Balance,
+    ) -> Self {
+        self.push(BatchAction::Transfer(deposit), |tx| tx.transfer(deposit))
+    }
+
+    /// Deploys the contract into the receiver account.
+    pub fn deploy(
+        self,
+        wasm_bytes: &[u8],
+    ) -> Self {
+        let code = wasm_bytes.to_vec();
+        let action = BatchAction::DeployContract {
+            code_len: code.len(),
+        };
+        self.push(action, |tx| tx.deploy_contract(code))
+    }
+
+    pub fn add_key(
+        self,
+        public_key: PublicKey,
+        access_key: AccessKey,
+    ) -> Self {
+        let action = BatchAction::AddKey {
+            public_key: public_key.clone(),
+        };
+        self.push(action, |tx| tx.add_key(public_key, access_key))
+    }
+
+    pub fn add_full_access_key(
+        self,
+        public_key: PublicKey,
+    ) -> Self {
+        self.add_key(public_key, AccessKey::full_access())
+    }
+
+    pub fn function_call(
+        self,
+        method: &str,
+        args: serde_json::Value,
+        gas: Gas,
+        deposit: Balance,
+    ) -> Self {
+        let bytes = args.to_string().into_bytes();
+        let action = BatchAction::FunctionCall {
+            method: method.to_string(),
+            args,
+            gas,
+            deposit,
+        };
+        self.push(action, |tx| {
+            tx.function_call(method.to_string(), bytes, gas.0, deposit)
+        })
+    }
+
+    pub fn actions(&self) -> &[BatchAction] {
+        &self.actions
+    }
+
+    /// Signs and submits the transaction.
+    ///
+    /// The function calls are recorded by the active
+    /// [`GasProfiler`](super::GasProfiler) and
+    /// [`StorageTracker`](super::StorageTracker) as a single call, to the
+    /// methods joined by `+`, and the actions are recorded by the active
+    /// [`Recorder`](super::Recorder).
+    ///
+    /// In the transcript, the transfers to a created account are it's initial
+    /// balance, and the function call that follows a deploy is it's init
+    /// call. Deploys without a call after them and added keys aren't
+    /// recorded. If the batch's own receipt failed, none of it's actions
+    /// were applied, so the account creations and deploys, which can't be
+    /// allowed to fail, aren't recorded either. The receipts that it's
+    /// function calls create don't change what was applied, so their
+    /// failures aren't considered.
+    pub fn submit<T>(self) -> BatchExecution<T>
+    where
+        T: DeserializeOwned,
+    {
+        assert!(
+            !self.actions.is_empty(),
+            "the batch from {} to {} has no actions",
+            self.signer.account_id(),
+            self.receiver_id
+        );
+        let methods: Vec<&str> = self
+            .actions
+            .iter()
+            .filter_map(|action| match action {
+                BatchAction::FunctionCall { method, .. } => Some(method.as_str()),
+                _ => None,
+            })
+            .collect();
+        let method = (!methods.is_empty()).then(|| methods.join("+"));
+
+        // a missing (or deleted) account uses no storage
+        let storage_usage = || {
+            self.signer
+                .borrow_runtime()
+                .view_account(self.receiver_id.as_str())
+                .map_or(0, |account| account.storage_usage)
+        };
+        let storage_before = method
+            .as_ref()
+            .filter(|_| storage::is_active())
+            .map(|_| storage_usage());
+        let inner: Execution<T> = runtime::submit(self.signer, self.tx);
+
+        if let Some(method) = &method {
+            gas_profile::record_call(&self.receiver_id, method, || inner.receipt_tree());
+        }
+        if let (Some(method), Some(before)) = (&method, storage_before) {
+            storage::record(|| StorageRecord {
+                contract_id: self.receiver_id.clone(),
+                method: method.clone(),
+                signer_id: self.signer.account_id(),
+                before,
+                after: storage_usage(),
+                deposit: self.actions.iter().map(BatchAction::deposit).sum(),
+                storage_price_per_byte: RuntimeCosts::new(self.signer).storage_price_per_byte,
+            });
+        }
+        for entry in transcript_entries(
+            &self.signer.account_id(),
+            &self.receiver_id,
+            &self.actions,
+            matches!(batch_status(&inner), ExecutionStatus::Failure(_)),
+        ) {
+            transcript::record(|| entry);
+        }
+
+        BatchExecution {
+            inner,
+            actions: self.actions,
+        }
+    }
+}
+
+impl BatchAction {
+    /// The balance that the action attaches, which the receiver gets.
+    pub fn deposit(&self) -> Balance {
+        match self {
+            Self::Transfer(deposit) | Self::FunctionCall { deposit, .. } => *deposit,
+            _ => 0,
+        }
+    }
+}
+
+/// The status of the batch's own receipt, or of the transaction if it was
+/// invalid, and so has no receipt.
+///
+/// The status of the `execution` itself is the one of the last receipt that
+/// it's function calls returned.
+fn batch_status<T>(execution: &Execution<T>) -> ExecutionStatus {
+    let tree = execution.receipt_tree();
+    match tree.children.into_iter().next() {
+        Some(receipt) => receipt.status,
+        None => tree.status,
+    }
+}
+
+/// The transcript entries that replay the `actions` of a batch.
+fn transcript_entries(
+    signer_id: &AccountId,
+    receiver_id: &AccountId,
+    actions: &[BatchAction],
+    failed: bool,
+) -> Vec<TranscriptEntry> {
+    let creates_account = actions.contains(&BatchAction::CreateAccount);
+    let transferred: Balance = actions
+        .iter()
+        .filter_map(|action| match action {
+            BatchAction::Transfer(amount) => Some(amount),
+            _ => None,
+        })
+        .sum();
+    let mut entries = vec![];
+    let mut actions = actions.iter().peekable();
+    while let Some(action) = actions.next() {
+        match action {
+            BatchAction::CreateAccount if !failed => entries.push(TranscriptEntry::CreateAccount {
+                account_id: receiver_id.clone(),
+                signer_id: signer_id.clone(),
+                balance: transferred.into(),
+            }),
+            BatchAction::CreateAccount | BatchAction::AddKey { .. } => {}
+            BatchAction::Transfer(_) if creates_account => {}
+            BatchAction::Transfer(amount) => entries.push(TranscriptEntry::Send {
+                sender_id: signer_id.clone(),
+                receiver_id: receiver_id.clone(),
+                amount: (*amount).into(),
+                failed,
+            }),
+            BatchAction::DeployContract { .. } => {
+                if let Some(BatchAction::FunctionCall {
+                    method,
+                    args,
+                    gas,
+                    deposit,
+                }) = actions.peek()
+                {
+                    if !failed {
+                        entries.push(TranscriptEntry::Deploy {
+                            contract_id: receiver_id.clone(),
+                            method: method.clone(),
+                            args: args.clone(),
+                            signer_id: signer_id.clone(),
+                            gas: gas.0.into(),
+                            deposit: (*deposit).into(),
+                        });
+                    }
+                    actions.next();
+                }
+            }
+            BatchAction::FunctionCall {
+                method,
+                args,
+                gas,
+                deposit,
+            } => entries.push(TranscriptEntry::Call {
+                contract_id: receiver_id.clone(),
+                method: method.clone(),
+                args: args.clone(),
+                signer_id: signer_id.clone(),
+                gas: gas.0.into(),
+                deposit: (*deposit).into(),
+                failed,
+            }),
+        }
+    }
+    entries
+}
+
+/// Creates a [`Batch`] from an account.
+pub trait BatchExt {
+    fn batch(
+        &self,
+        receiver_id: AccountId,
+    ) -> Batch<'_>;
+}
+
+impl BatchExt for UserAccount {
+    fn batch(
+        &self,
+        receiver_id: AccountId,
+    ) -> Batch<'_> {
+        Batch::new(self, receiver_id)
+    }
+}
+
+/// The execution of a [`Batch`], with the outcome of each of it's actions.
+///
+/// Only the actions themselves are considered, and not the receipts that a
+/// function call may create.
+#[must_use]
+pub struct BatchExecution<T> {
+    pub inner: Execution<T>,
+    pub actions: Vec<BatchAction>,
+}
+
+impl<T> std::ops::Deref for BatchExecution<T> {
+    type Target = Execution<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl<T> BatchExecution<T> {
+    /// The outcome of each action, in order.
+    pub fn outcomes(&self) -> Vec<ActionOutcome> {
+        let failure = match batch_status(&self.inner) {
+            ExecutionStatus::Failure(err) => Some(err),
+            _ => None,
+        };
+        let failed_index = match &failure {
+            None => None,
+            Some(TxExecutionError::ActionError(ActionError {
+                index: Some(index), ..
+            })) => Some(*index as usize),
+            // the transaction was invalid, or it's receipt failed before
+            // executing any action
+            Some(err) => return vec![ActionOutcome::Invalid(err.clone()); self.actions.len()],
+        };
+
+        (0..self.actions.len())
+            .map(|index| match failed_index {
+                None => ActionOutcome::Success,
+                Some(failed) if index < failed => ActionOutcome::Success,
+                Some(failed) if index == failed => ActionOutcome::Failure(failure.clone().unwrap()),
+                Some(_) => ActionOutcome::NotExecuted,
+            })
+            .collect()
+    }
+
+    pub fn outcome(
+        &self,
+        index: usize,
+    ) -> ActionOutcome {
+        self.outcomes()
+            .into_iter()
+            .nth(index)
+            .unwrap_or_else(|| panic!("the batch has no action #{}", index))
+    }
+
+    pub fn assert_action_success(
+        &self,
+        index: usize,
+    ) {
+        let outcome = self.outcome(index);
+        if outcome != ActionOutcome::Success {
+            panic!(
+                "expected action #{} ({:?}) to succeed, but got {:?}:\n{}",
+                index,
+                self.actions[index],
+                outcome,
+                self.receipt_tree()
+            );
+        }
+    }
+
+    /// Asserts that the action failed with the `expected` kind of error, or
+    /// that the transaction was invalid with it.
+    pub fn assert_action_failure(
+        &self,
+        index: usize,
+        expected: FailureKind,
+    ) {
+        match self.outcome(index) {
+            ActionOutcome::Failure(err) | ActionOutcome::Invalid(err) if expected.matches(&err) => {
+            }
+            outcome => panic!(
+                "expected action #{} ({:?}) to fail with {:?}, but got {:?}:\n{}",
+                index,
+                self.actions[index],
+                expected,
+                outcome,
+                self.receipt_tree()
+            ),
+        }
+    }
+
+    pub fn map<M>(self) -> BatchExecution<M>
+    where
+        M: DeserializeOwned,
+    {
+        BatchExecution {
+            inner: self.inner.map(),
+            actions: self.actions,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gas::TGAS;
+    use crate::sim::contract_ext::{deploy_test_contract, TEST_CONTRACT_WASM};
+    use crate::sim::{GasProfiler, Recorder, StorageTracker};
+    use near_sdk::serde_json::json;
+    use near_sdk_sim::{init_simulator, to_yocto};
+
+    #[test]
+    fn create_and_transfer() {
+        let root = init_simulator(None);
+        let carol_id: AccountId = "carol".parse().unwrap();
+        let execution = root
+            .batch(carol_id.clone())
+            .create_account()
+            .transfer(to_yocto("10"))
+            .submit::<()>();
+
+        assert_eq!(
+            execution.outcomes(),
+            [ActionOutcome::Success, ActionOutcome::Success]
+        );
+        let carol = root
+            .borrow_runtime()
+            .view_account(carol_id.as_str())
+            .unwrap();
+        assert_eq!(carol.amount, to_yocto("10"));
+    }
+
+    #[test]
+    fn atomic() {
+        let root = init_simulator(None);
+        let carol_id: AccountId = "carol".parse().unwrap();
+        let execution = root
+            .batch(carol_id.clone())
+            .create_account()
+            .transfer(to_yocto("10"))
+            // carol has no contract
+            .function_call("new", json!({}), near_sdk::Gas(10 * TGAS), 0)
+            .transfer(1)
+            .submit::<()>();
+
+        execution.assert_action_success(1);
+        assert!(matches!(execution.outcome(2), ActionOutcome::Failure(_)));
+        assert_eq!(execution.outcome(3), ActionOutcome::NotExecuted);
+        // nothing was applied
+        assert!(root
+            .borrow_runtime()
+            .view_account(carol_id.as_str())
+            .is_none());
+    }
+
+    #[test]
+    fn downstream_failure() {
+        let root = runtime::init_simulator(None);
+        let contract = deploy_test_contract(&root, "contract");
+        let recorder = Recorder::new();
+        let gas = Gas(100 * TGAS);
+        let execution = recorder.record(|| {
+            root.batch(contract.user_account.account_id())
+                .function_call("forward", json!("missing"), gas, 0)
+                .transfer(1)
+                .submit::<()>()
+        });
+
+        // the receipt that `forward` created failed, but the batch's actions
+        // were applied
+        assert!(execution
+            .receipt_tree()
+            .find_failure(&FailureKind::AccountDoesNotExist(
+                "missing".parse().unwrap()
+            ))
+            .is_some());
+        assert_eq!(
+            execution.outcomes(),
+            [ActionOutcome::Success, ActionOutcome::Success]
+        );
+        assert_eq!(
+            recorder.entries(),
+            [
+                TranscriptEntry::Call {
+                    contract_id: "contract".parse().unwrap(),
+                    method: "forward".into(),
+                    args: json!("missing"),
+                    signer_id: root.account_id(),
+                    gas: gas.0.into(),
+                    deposit: 0.into(),
+                    failed: false,
+                },
+                TranscriptEntry::Send {
+                    sender_id: root.account_id(),
+                    receiver_id: "contract".parse().unwrap(),
+                    amount: 1.into(),
+                    failed: false,
+                },
+            ]
+        );
+
+        // when the call is the last action, the batch returns it's promise,
+        // so the final status of the transaction is the failure
+        let execution = root
+            .batch(contract.user_account.account_id())
+            .transfer(1)
+            .function_call("forward", json!("missing"), gas, 0)
+            .submit::<()>();
+        assert!(matches!(execution.status(), ExecutionStatus::Failure(_)));
+        assert_eq!(
+            execution.outcomes(),
+            [ActionOutcome::Success, ActionOutcome::Success]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "expected action #0 (CreateAccount) to fail")]
+    fn assert_failure() {
+        let root = init_simulator(None);
+        root.batch("carol".parse().unwrap())
+            .create_account()
+            .transfer(to_yocto("10"))
+            .submit::<()>()
+            .assert_action_failure(0, FailureKind::LackBalanceForState);
+    }
+
+    #[test]
+    fn account_already_exists() {
+        let root = init_simulator(None);
+        let carol = root.create_user("carol".parse().unwrap(), to_yocto("10"));
+        let execution = root
+            .batch(carol.account_id())
+            .create_account()
+            .transfer(to_yocto("10"))
+            .submit::<()>();
+
+        execution.assert_action_failure(0, FailureKind::AccountAlreadyExists(carol.account_id()));
+        assert_eq!(execution.outcome(1), ActionOutcome::NotExecuted);
+        assert_eq!(carol.account().unwrap().amount, to_yocto("10"));
+    }
+
+    #[test]
+    fn invalid() {
+        let root = runtime::init_simulator(None);
+        let carol = root.create_user("carol".parse().unwrap(), to_yocto("1"));
+        let execution = carol
+            .batch(root.account_id())
+            .transfer(to_yocto("10"))
+            .transfer(1)
+            .submit::<()>();
+
+        assert!(matches!(
+            execution.outcomes()[..],
+            [ActionOutcome::Invalid(_), ActionOutcome::Invalid(_)]
+        ));
+        execution.assert_action_failure(1, FailureKind::NotEnoughBalance);
+        assert_eq!(carol.account().unwrap().amount, to_yocto("1"));
+    }
+
+    #[test]
+    fn recorded() {
+        let root = runtime::init_simulator(None);
+        let recorder = Recorder::new();
+        let profiler = GasProfiler::new();
+        let tracker = StorageTracker::new();
+        let gas = Gas(10 * TGAS);
+        recorder.record(|| {
+            profiler.profile(|| {
+                tracker.track(|| {
+                    root.batch("contract".parse().unwrap())
+                        .create_account()
+                        .transfer(to_yocto("10"))
+                        .deploy(TEST_CONTRACT_WASM)
+                        .function_call("new", json!({}), gas, 0)
+                        .function_call("write", json!("hello"), gas, 1)
+                        .submit::<()>()
+                        .assert_success();
+                })
+            })
+        });
+
+        assert_eq!(
+            recorder.entries(),
+            [
+                TranscriptEntry::CreateAccount {
+                    account_id: "contract".parse().unwrap(),
+                    signer_id: root.account_id(),
+                    balance: to_yocto("10").into(),
+                },
+                TranscriptEntry::Deploy {
+                    contract_id: "contract".parse().unwrap(),
+                    method: "new".into(),
+                    args: json!({}),
+                    signer_id: root.account_id(),
+                    gas: gas.0.into(),
+                    deposit: 0.into(),
+                },
+                TranscriptEntry::Call {
+                    contract_id: "contract".parse().unwrap(),
+                    method: "write".into(),
+                    args: json!("hello"),
+                    signer_id: root.account_id(),
+                    gas: gas.0.into(),
+                    deposit: 1.into(),
+                    failed: false,
+                },
+            ]
+        );
+        let report = profiler.report();
+        let methods: Vec<&String> = report.methods.keys().collect();
+        assert_eq!(methods, ["contract::new+write"]);
+        let records = tracker.records();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].method, "new+write");
+        assert_eq!(records[0].before, 0);
+        assert!(records[0].after as usize > TEST_CONTRACT_WASM.len());
+        assert_eq!(records[0].deposit, to_yocto("10") + 1);
+    }
+
+    #[test]
+    fn recorded_failure() {
+        let root = init_simulator(None);
+        let recorder = Recorder::new();
+        let execution = recorder.record(|| {
+            root.batch("carol".parse().unwrap())
+                .create_account()
+                .transfer(to_yocto("10"))
+                .function_call("new", json!({}), Gas(10 * TGAS), 0)
+                .submit::<()>()
+        });
+
+        assert!(matches!(execution.outcome(2), ActionOutcome::Failure(_)));
+        // the account wasn't created, but the call is replayed as failing
+        assert_eq!(
+            recorder.entries(),
+            [TranscriptEntry::Call {
+                contract_id: "carol".parse().unwrap(),
+                method: "new".into(),
+                args: json!({}),
+                signer_id: root.account_id(),
+                gas: (10 * TGAS).into(),
+                deposit: 0.into(),
+                failed: true,
+            }]
+        );
+    }
+}
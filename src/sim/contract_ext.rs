@@ -1,4 +1,11 @@
+use super::costs::RuntimeCosts;
+use super::debug::{debugln, Verbosity};
 use super::execution_ext::ExecutionExt;
+use super::gas_profile;
+use super::receipt_tree::ReceiptNode;
+use super::runtime::{self, Receipts};
+use super::storage::{self, StorageRecord};
+use super::transcript::{self, TranscriptEntry};
 use near_sdk::utils::WithAccount;
 use near_sdk::{serde::Deserialize, AccountId, Balance, Gas};
 use near_sdk_sim::{ExecutionResult, UserAccount, ViewResult};
@@ -26,92 +33,61 @@ impl<T> ContractAcc for near_sdk_sim::ContractAccount<T> {
 }
 
 pub trait ContractExt: ContractAcc {
+    /// Balance that the contract must keep for it's storage, besides what
+    /// is already covered by it's locked balance.
+    ///
+    /// The storage usage includes the account and access key records.
+    fn storage_requirement(&self) -> Balance {
+        let account = self.account().unwrap();
+        let costs = RuntimeCosts::new(self.user_account());
+        costs
+            .storage_cost(account.storage_usage)
+            .saturating_sub(account.locked)
+    }
+
     /// Calculates any extra deposit on the contract not required by it's
     /// storage cost.
-    #[allow(clippy::let_and_return)]
-    fn get_skewed_extra_deposit(&self) -> u128 {
-        // TODO: find out why there is such a constant.
-        //
-        /// Constant observed from empirical tests.
-        ///
-        /// After the contract transfered all of it's funds (except for
-        /// it's storage requirements and the LEEWAY_CONST), it should have
-        /// zero funds left, but in practice it appears to still have
-        /// this const amount to it.
-        ///
-        /// I don't know why this happens.
-        const SKEWED_CONST: u128 = 669547687500000000;
-
-        let storage_usage: u64 = self.account().unwrap().storage_usage;
-        let storage_cost_test: u128 = near_sdk::env::STORAGE_PRICE_PER_BYTE;
-        let storage_cost_total: u128 = storage_usage as u128 * storage_cost_test;
-
+    fn get_extra_deposit(&self) -> Balance {
         let amount = self.account().unwrap().amount;
+        amount.saturating_sub(self.storage_requirement())
+    }
 
-        // println!("storage_usage: {}", storage_usage);
-        // println!(
-        //     "storage_cost_total: {} ={}",
-        //     storage_cost_total,
-        //     pretty_near(storage_cost_total)
-        // );
-        // println!(
-        //     "extra deposit: {} ={}",
-        //     amount - storage_cost_total,
-        //     pretty_near(amount - storage_cost_total)
-        // );
-        let skewed_extra_deposit = amount - storage_cost_total - SKEWED_CONST;
-        // println!(
-        //     "skewed extra deposit: {}, ={}",
-        //     skewed_extra_deposit,
-        //     pretty_near(skewed_extra_deposit)
-        // );
-        skewed_extra_deposit
+    /// Calculates any extra deposit on the contract not required by it's
+    /// storage cost, disregarding the gas refund that the contract receives
+    /// after it sends a transfer.
+    ///
+    /// This is zero right after
+    /// [`transfer_extra_deposit_to`](ContractExt::transfer_extra_deposit_to).
+    fn get_skewed_extra_deposit(&self) -> u128 {
+        let costs = RuntimeCosts::new(self.user_account());
+        self.get_extra_deposit()
+            .saturating_sub(costs.transfer_refund())
     }
 
     /// Calculates any extra deposit on the contract not required by it's
     /// storage cost and transfer to some other account.
+    ///
+    /// The contract also pays for the transfer itself, so nothing is
+    /// transferred if the extra deposit doesn't exceed that cost.  
+    /// Returns the transferred amount.
     fn transfer_extra_deposit_to(
         &self,
         receiver: &UserAccount,
-    ) {
-        let skewed_extra = self.get_skewed_extra_deposit();
-        // TODO: test when the extra is otherwise still too small
-        // (eg. 1 yoctoNear)
-        if skewed_extra == 0 {
-            // has no extra to transfer
-            return;
-        }
-
-        // TODO: find out why there is such a constant.
-        //
-        /// Constant observed from empirical tests.
-        ///
-        /// Besides the storage requirements, the contracts appear
-        /// to require this fixed amount.
-        ///
-        /// I don't know why this happens.
-        const LEEWAY_CONST: u128 = 45306060187500000000;
-
-        // println!("--");
-        let storage_usage: u64 = self.account().unwrap().storage_usage;
-        let storage_cost_test: u128 = near_sdk::env::STORAGE_PRICE_PER_BYTE;
-        let storage_cost_total: u128 = storage_usage as u128 * storage_cost_test;
-
-        let amount = self.account().unwrap().amount;
-
-        if amount < storage_cost_total + LEEWAY_CONST {
-            // cancel if the calculation would underflow
-            return;
+    ) -> Balance {
+        let costs = RuntimeCosts::new(self.user_account());
+        let extra = self.get_extra_deposit();
+        let cost = costs.transfer_cost();
+        if extra <= cost {
+            // has no extra to transfer, after paying for the transfer
+            return 0;
         }
 
-        let extra = amount - storage_cost_total - LEEWAY_CONST;
-
-        // println!("extra deposit: {} ={}", extra, pretty_near(extra));
-        self.user_account().transfer(receiver.account_id(), extra);
+        let amount = extra - cost;
+        transcript::transfer(self.user_account(), receiver.account_id(), amount).assert_success();
 
         let skewed_extra = self.get_skewed_extra_deposit();
         assert_eq!(skewed_extra, 0);
-        // println!("--");
+        amount
     }
 
     fn debug_json_call<Result>(
@@ -128,8 +104,9 @@ pub trait ContractExt: ContractAcc {
         let method_name = method.to_string();
         let contract_id = self.account_id();
         let args_str = near_sdk::serde_json::to_string(&args).unwrap();
-        println!("--- call debug ---");
-        println!(
+        debugln!(Verbosity::Commands, "--- call debug ---");
+        debugln!(
+            Verbosity::Commands,
             "near call {contract} {method_name} '{args}' --accountId {signer} --gas {gas} --depositYocto {deposit}",
             contract = &contract_id,
             method_name = &method_name,
@@ -144,48 +121,50 @@ pub trait ContractExt: ContractAcc {
         use near_sdk_sim::transaction::ExecutionStatus;
         match res.status() {
             ExecutionStatus::Unknown => {
-                println!("--- unknown response ---");
+                debugln!(Verbosity::Responses, "--- unknown response ---");
             }
             ExecutionStatus::Failure(e) => {
-                println!("--- failed response ---");
-                println!("{}", e);
+                debugln!(Verbosity::Responses, "--- failed response ---");
+                debugln!(Verbosity::Responses, "{}", e);
             }
             ExecutionStatus::SuccessReceiptId(receipt_id) => {
-                println!("--- receipt response ---");
-                println!(
+                debugln!(Verbosity::Responses, "--- receipt response ---");
+                debugln!(
+                    Verbosity::Responses,
                     "{}…",
                     receipt_id.to_string().chars().take(6).collect::<String>()
                 );
             }
             ExecutionStatus::SuccessValue(v) => {
                 if v.is_empty() {
-                    println!("--- js response ---");
-                    println!("null");
+                    debugln!(Verbosity::Responses, "--- js response ---");
+                    debugln!(Verbosity::Responses, "null");
                 } else {
                     match near_sdk::serde_json::from_slice::<near_sdk::serde_json::Value>(&v) {
                         Ok(value) => {
-                            println!("--- json response ---");
-                            println!(
+                            debugln!(Verbosity::Responses, "--- json response ---");
+                            debugln!(
+                                Verbosity::Responses,
                                 "{}",
                                 near_sdk::serde_json::to_string_pretty(&value).unwrap()
                             );
                         }
                         Err(_) => match String::from_utf8(v.clone()) {
                             Ok(s) => {
-                                println!("--- utf8 response ---");
-                                println!("{}", s);
+                                debugln!(Verbosity::Responses, "--- utf8 response ---");
+                                debugln!(Verbosity::Responses, "{}", s);
                             }
                             Err(_) => {
-                                println!("--- base64 response ---");
+                                debugln!(Verbosity::Responses, "--- base64 response ---");
                                 let b64 = base64::encode(&v);
-                                println!("{}", b64);
+                                debugln!(Verbosity::Responses, "{}", b64);
                             }
                         },
                     };
                 }
             }
         }
-        println!("--- end debug ---");
+        debugln!(Verbosity::Commands, "--- end debug ---");
         res
     }
 
@@ -200,9 +179,40 @@ pub trait ContractExt: ContractAcc {
     where
         Result: near_sdk::serde::de::DeserializeOwned,
     {
-        let tx = Self::pending_tx_json_call(self.account_id(), method, args);
-        let res = caller.function_call(tx, gas.0, deposit);
-        Execution::new(res)
+        // a missing (or deleted) account uses no storage
+        let storage_usage = || self.account().map_or(0, |account| account.storage_usage);
+        let storage_before = storage::is_active().then(storage_usage);
+        let tx = runtime::transaction(caller, &self.account_id()).function_call(
+            method.to_string(),
+            args.to_string().into_bytes(),
+            gas.0,
+            deposit,
+        );
+        let res: Execution<Result> = runtime::submit(caller, tx);
+        gas_profile::record_call(&self.account_id(), method, || res.receipt_tree());
+        if let Some(before) = storage_before {
+            storage::record(|| StorageRecord {
+                contract_id: self.account_id(),
+                method: method.to_string(),
+                signer_id: caller.account_id(),
+                before,
+                after: storage_usage(),
+                deposit,
+                storage_price_per_byte: RuntimeCosts::new(self.user_account())
+                    .storage_price_per_byte,
+            });
+        }
+        transcript::record(|| TranscriptEntry::Call {
+            contract_id: self.account_id(),
+            method: method.to_string(),
+            args,
+            signer_id: caller.account_id(),
+            gas: gas.0.into(),
+            deposit: deposit.into(),
+            // a receipt may fail even if the call's result doesn't
+            failed: !res.receipt_tree().failures().is_empty(),
+        });
+        res
     }
 
     fn debug_json_deploy(
@@ -219,8 +229,9 @@ pub trait ContractExt: ContractAcc {
     {
         let method_name = method.to_string();
         let args_str = near_sdk::serde_json::to_string(&args).unwrap();
-        println!("--- deploy debug ---");
-        println!(
+        debugln!(Verbosity::Commands, "--- deploy debug ---");
+        debugln!(
+            Verbosity::Commands,
             "near deploy --wasmFile \"WASM_PATH\" --contractName \"{contract}\" --initFunction \"{method_name}\" --initArgs '{args}' --initGas \"{gas}\", --initDeposit \"{deposit}\"",
             contract = &contract_id,
             method_name = &method_name,
@@ -248,10 +259,20 @@ pub trait ContractExt: ContractAcc {
         let account_id = near_sdk::AccountId::new_unchecked(contract_id.to_string());
         let __contract = Self::Contract::with_account(account_id.clone());
 
-        let tx = Self::pending_tx_json_call(account_id, method, args);
+        let tx = Self::pending_tx_json_call(account_id.clone(), method, args.clone());
+
+        let user_account = root.deploy_and_initialize(wasm_bytes, tx, deposit, gas.0);
+        transcript::record(|| TranscriptEntry::Deploy {
+            contract_id: account_id,
+            method: method.to_string(),
+            args,
+            signer_id: root.account_id(),
+            gas: gas.0.into(),
+            deposit: deposit.into(),
+        });
 
         near_sdk_sim::ContractAccount {
-            user_account: root.deploy_and_initialize(wasm_bytes, tx, deposit, gas.0),
+            user_account,
             contract: __contract,
         }
     }
@@ -280,8 +301,9 @@ pub trait ContractExt: ContractAcc {
         let method_name = method.to_string();
         let contract_id = self.account_id();
         let args_str = near_sdk::serde_json::to_string(&args).unwrap();
-        println!("--- view debug ---");
-        println!(
+        debugln!(Verbosity::Commands, "--- view debug ---");
+        debugln!(
+            Verbosity::Commands,
             "near view {contract} {method_name} '{args}'",
             contract = &contract_id,
             method_name = &method_name,
@@ -293,37 +315,38 @@ pub trait ContractExt: ContractAcc {
         if res.is_ok() {
             let v = res.unwrap();
             if v.is_empty() {
-                println!("--- js response ---");
-                println!("null");
+                debugln!(Verbosity::Responses, "--- js response ---");
+                debugln!(Verbosity::Responses, "null");
             } else {
                 match near_sdk::serde_json::from_slice::<near_sdk::serde_json::Value>(&v) {
                     Ok(value) => {
-                        println!("--- json response ---");
-                        println!(
+                        debugln!(Verbosity::Responses, "--- json response ---");
+                        debugln!(
+                            Verbosity::Responses,
                             "{}",
                             near_sdk::serde_json::to_string_pretty(&value).unwrap()
                         );
                     }
                     Err(_) => match String::from_utf8(v.clone()) {
                         Ok(s) => {
-                            println!("--- utf8 response ---");
-                            println!("{}", s);
+                            debugln!(Verbosity::Responses, "--- utf8 response ---");
+                            debugln!(Verbosity::Responses, "{}", s);
                         }
                         Err(_) => {
-                            println!("--- base64 response ---");
+                            debugln!(Verbosity::Responses, "--- base64 response ---");
                             let b64 = base64::encode(&v);
-                            println!("{}", b64);
+                            debugln!(Verbosity::Responses, "{}", b64);
                         }
                     },
                 };
             }
         } else {
-            println!("--- failed response ---");
+            debugln!(Verbosity::Responses, "--- failed response ---");
             let err = res.unwrap_err();
-            println!("{} - {:?}", &err, &err);
+            debugln!(Verbosity::Responses, "{} - {:?}", &err, &err);
         }
 
-        println!("--- end debug ---");
+        debugln!(Verbosity::Commands, "--- end debug ---");
         res
     }
 
@@ -337,7 +360,16 @@ pub trait ContractExt: ContractAcc {
     {
         let res = self
             .user_account()
-            .view_method_call(Self::pending_tx_json_view(self.account_id(), method, args));
+            .view_method_call(Self::pending_tx_json_view(
+                self.account_id(),
+                method,
+                args.clone(),
+            ));
+        transcript::record(|| TranscriptEntry::View {
+            contract_id: self.account_id(),
+            method: method.to_string(),
+            args,
+        });
         View::new(res)
     }
 
@@ -407,6 +439,9 @@ impl<T> std::ops::Deref for View<T> {
 #[must_use]
 pub struct Execution<T> {
     pub inner: ExecutionResult,
+    /// The transaction that was submitted and it's receipts, if they are
+    /// known.
+    transaction: Option<(ExecutionResult, Receipts)>,
     _result: PhantomData<T>,
 }
 
@@ -425,21 +460,47 @@ where
     pub fn new(result: ExecutionResult) -> Self {
         Self {
             inner: result,
+            transaction: None,
             _result: PhantomData,
         }
     }
 
+    /// Sets the `transaction` that was submitted, and it's `receipts`.
+    pub fn with_transaction(
+        mut self,
+        transaction: ExecutionResult,
+        receipts: Receipts,
+    ) -> Self {
+        self.transaction = Some((transaction, receipts));
+        self
+    }
+
     pub fn unwrap_json(&self) -> T {
         self.inner.unwrap_json()
     }
 }
 
 impl<T> Execution<T> {
+    /// Tree of the transaction and all receipts that it created.
+    ///
+    /// If the transaction isn't known, the tree starts at it's final
+    /// receipt, and the receipts have no actions.
+    pub fn receipt_tree(&self) -> ReceiptNode {
+        match &self.transaction {
+            Some((transaction, receipts)) => ReceiptNode::with_receipts(transaction, receipts),
+            None => ReceiptNode::new(&self.inner),
+        }
+    }
+
     pub fn map<M>(self) -> Execution<M>
     where
         M: near_sdk::serde::de::DeserializeOwned,
     {
-        Execution::<M>::new(self.inner)
+        Execution {
+            inner: self.inner,
+            transaction: self.transaction,
+            _result: PhantomData,
+        }
     }
 }
 
@@ -642,3 +703,96 @@ impl WithAccount for DummyContract {
         Self { account_id }
     }
 }
+
+/// `res/test_contract.wasm`, which has no dependencies, so the tests can make
+/// calls that change the storage or create receipts.
+#[cfg(test)]
+pub(crate) const TEST_CONTRACT_WASM: &[u8] = include_bytes!("../../res/test_contract.wasm");
+
+/// Deploys the [`TEST_CONTRACT_WASM`].
+#[cfg(test)]
+pub(crate) fn deploy_test_contract(
+    root: &UserAccount,
+    contract_id: &str,
+) -> near_sdk_sim::ContractAccount<DummyContract> {
+    let account_id: AccountId = contract_id.parse().unwrap();
+    near_sdk_sim::ContractAccount {
+        user_account: root.deploy(
+            TEST_CONTRACT_WASM,
+            account_id.clone(),
+            near_sdk_sim::to_yocto("10"),
+        ),
+        contract: DummyContract::with_account(account_id),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk_sim::{init_simulator, to_yocto, ContractAccount};
+
+    /// Creates an account that has exactly `extra` besides it's storage
+    /// requirement.
+    fn account_with_extra(
+        root: &UserAccount,
+        id: &str,
+        extra: Balance,
+    ) -> ContractAccount<DummyContract> {
+        // the storage usage of a new account doesn't depend on it's id
+        let probe_id: AccountId = format!("probe-{}", id).parse().unwrap();
+        let probe = ContractAccount {
+            user_account: root.create_user(probe_id.clone(), to_yocto("1")),
+            contract: DummyContract::with_account(probe_id),
+        };
+        let required = probe.storage_requirement();
+
+        let account_id: AccountId = id.parse().unwrap();
+        let account = ContractAccount {
+            user_account: root.create_user(account_id.clone(), required + extra),
+            contract: DummyContract::with_account(account_id),
+        };
+        assert_eq!(account.get_extra_deposit(), extra);
+        account
+    }
+
+    #[test]
+    fn transfer_extra() {
+        let root = init_simulator(None);
+        let costs = RuntimeCosts::new(&root);
+        let receiver = root.create_user("receiver".parse().unwrap(), to_yocto("1"));
+        let before = receiver.account().unwrap().amount;
+
+        let extra = to_yocto("1");
+        let contract = account_with_extra(&root, "contract", extra);
+        let transferred = contract.transfer_extra_deposit_to(&receiver);
+        assert_eq!(transferred, extra - costs.transfer_cost());
+        assert_eq!(receiver.account().unwrap().amount, before + transferred);
+
+        // only the gas refund is left
+        assert_eq!(contract.get_extra_deposit(), costs.transfer_refund());
+        assert_eq!(contract.get_skewed_extra_deposit(), 0);
+        assert_eq!(contract.transfer_extra_deposit_to(&receiver), 0);
+    }
+
+    #[test]
+    fn tiny_extra() {
+        let root = init_simulator(None);
+        let costs = RuntimeCosts::new(&root);
+        let receiver = root.create_user("receiver".parse().unwrap(), to_yocto("1"));
+        let before = receiver.account().unwrap().amount;
+
+        // can't pay for the transfer
+        for (id, extra) in [("none", 0), ("one", 1), ("cost", costs.transfer_cost())] {
+            let contract = account_with_extra(&root, id, extra);
+            assert_eq!(contract.transfer_extra_deposit_to(&receiver), 0);
+            assert_eq!(contract.get_extra_deposit(), extra);
+        }
+        assert_eq!(receiver.account().unwrap().amount, before);
+
+        // can pay for the transfer, with a single yocto left to transfer
+        let contract = account_with_extra(&root, "more", costs.transfer_cost() + 1);
+        assert_eq!(contract.transfer_extra_deposit_to(&receiver), 1);
+        assert_eq!(receiver.account().unwrap().amount, before + 1);
+        assert_eq!(contract.get_skewed_extra_deposit(), 0);
+    }
+}
@@ -0,0 +1,85 @@
+use near_sdk::{Balance, Gas};
+use near_sdk_sim::UserAccount;
+
+/// Balance-related settings taken from the simulator's runtime config.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RuntimeCosts {
+    /// Balance that must be kept per byte of storage.
+    pub storage_price_per_byte: Balance,
+    /// Price of the gas burnt by transactions and receipts.
+    pub gas_price: Balance,
+    /// Inflation ratio (numerator and denominator) applied to the gas price
+    /// for gas that is prepaid for the execution of a receipt.
+    pub pessimistic_inflation: (u128, u128),
+    /// Gas burnt when sending a transfer to some other account.
+    pub transfer_send_gas: Gas,
+    /// Gas prepaid for executing a transfer.
+    pub transfer_exec_gas: Gas,
+}
+
+impl RuntimeCosts {
+    /// Reads the costs from the runtime that the `user` is in.
+    pub fn new(user: &UserAccount) -> Self {
+        let runtime = user.borrow_runtime();
+        let config = &runtime.genesis.runtime_config;
+        let fees = &config.transaction_costs;
+        let receipt = &fees.action_receipt_creation_config;
+        let transfer = &fees.action_creation_config.transfer_cost;
+        let ratio = &fees.pessimistic_gas_price_inflation_ratio;
+        Self {
+            storage_price_per_byte: config.storage_amount_per_byte,
+            gas_price: runtime.genesis.gas_price,
+            pessimistic_inflation: (*ratio.numer() as u128, *ratio.denom() as u128),
+            transfer_send_gas: Gas(receipt.send_not_sir + transfer.send_not_sir),
+            transfer_exec_gas: Gas(receipt.execution + transfer.execution),
+        }
+    }
+
+    /// Balance that must be kept for `bytes` of storage.
+    pub fn storage_cost(
+        &self,
+        bytes: u64,
+    ) -> Balance {
+        bytes as u128 * self.storage_price_per_byte
+    }
+
+    /// Gas price paid for gas that is prepaid for a receipt that is
+    /// executed on the next block, rounded up.
+    pub fn pessimistic_gas_price(&self) -> Balance {
+        let (numer, denom) = self.pessimistic_inflation;
+        (self.gas_price * numer + denom - 1) / denom
+    }
+
+    /// Balance that the sender of a transfer pays, besides the transferred
+    /// amount.
+    pub fn transfer_cost(&self) -> Balance {
+        self.gas_price * self.transfer_send_gas.0 as u128
+            + self.pessimistic_gas_price() * self.transfer_exec_gas.0 as u128
+    }
+
+    /// Balance that is refunded to the sender of a transfer once it's
+    /// executed, since the execution gas was prepaid at the pessimistic gas
+    /// price.
+    pub fn transfer_refund(&self) -> Balance {
+        (self.pessimistic_gas_price() - self.gas_price) * self.transfer_exec_gas.0 as u128
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_config() {
+        let root = near_sdk_sim::init_simulator(None);
+        let costs = RuntimeCosts::new(&root);
+        assert_eq!(
+            costs.storage_price_per_byte,
+            near_sdk::env::STORAGE_PRICE_PER_BYTE
+        );
+        // amounts that were previously hardcoded after being observed on
+        // the default config
+        assert_eq!(costs.transfer_refund(), 669547687500000000);
+        assert_eq!(costs.transfer_cost(), 45306060187500000000);
+    }
+}
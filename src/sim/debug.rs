@@ -0,0 +1,244 @@
+//! Where, and how much of, the debug output is written to.
+//!
+//! The `debug_*` methods of [`ContractExt`](super::ContractExt) and
+//! [`ExecutionExt::pretty_debug`](super::ExecutionExt::pretty_debug) write to
+//! the current [`DebugConfig`], which is the one set for the current thread
+//! (and so for the current test) or otherwise the global one.
+//!
+//! The global config prints only the commands, unless the `SIM_DEBUG` env
+//! var sets another verbosity (see [`DebugConfig::from_env`]).
+
+use super::scoped::Scoped;
+use once_cell::sync::Lazy;
+use std::fs::File;
+use std::path::Path;
+use std::sync::{Arc, Mutex, RwLock};
+
+/// How much debug output is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Verbosity {
+    /// Nothing is written.
+    Silent,
+    /// Only the `near` CLI commands that reproduce each call, view and
+    /// deploy.
+    Commands,
+    /// Also the responses.
+    Responses,
+    /// Also the status, gas and logs of each receipt.
+    Receipts,
+}
+
+impl std::str::FromStr for Verbosity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "silent" => Ok(Self::Silent),
+            "commands" => Ok(Self::Commands),
+            "responses" => Ok(Self::Responses),
+            "receipts" => Ok(Self::Receipts),
+            _ => Err(format!(
+                "unknown verbosity \"{}\", expected one of: silent, commands, responses, receipts",
+                s
+            )),
+        }
+    }
+}
+
+/// Where the debug output is written to.
+#[derive(Debug, Clone)]
+pub enum DebugSink {
+    /// Discards the output.
+    Silent,
+    /// Prints to stdout, which is captured by the test harness.
+    Stdout,
+    /// Appends to a shared buffer.
+    Buffer(Arc<Mutex<String>>),
+    /// Appends to a file.
+    File(Arc<Mutex<File>>),
+}
+
+impl DebugSink {
+    /// A new, empty, buffer.
+    pub fn buffer() -> Self {
+        Self::Buffer(Default::default())
+    }
+
+    /// Appends to the file at `path`, creating it if needed.
+    pub fn file(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        Ok(Self::File(Arc::new(Mutex::new(file))))
+    }
+
+    /// Takes what was written to a buffer, leaving it empty.
+    /// Other sinks have nothing to take.
+    pub fn take(&self) -> String {
+        match self {
+            Self::Buffer(buffer) => std::mem::take(&mut *buffer.lock().unwrap()),
+            Self::Silent | Self::Stdout | Self::File(_) => String::new(),
+        }
+    }
+
+    pub fn write_line(
+        &self,
+        args: std::fmt::Arguments,
+    ) {
+        use std::fmt::Write as _;
+        use std::io::Write as _;
+
+        match self {
+            Self::Silent => {}
+            Self::Stdout => println!("{}", args),
+            Self::Buffer(buffer) => writeln!(buffer.lock().unwrap(), "{}", args).unwrap(),
+            Self::File(file) => writeln!(file.lock().unwrap(), "{}", args)
+                .expect("failed to write the debug output to the file"),
+        }
+    }
+}
+
+/// Where, and how much of, the debug output is written to.
+#[derive(Debug, Clone)]
+pub struct DebugConfig {
+    pub sink: DebugSink,
+    pub verbosity: Verbosity,
+}
+
+impl DebugConfig {
+    pub fn new(
+        sink: DebugSink,
+        verbosity: Verbosity,
+    ) -> Self {
+        Self { sink, verbosity }
+    }
+
+    /// Writes nothing.
+    pub fn silent() -> Self {
+        Self::new(DebugSink::Silent, Verbosity::Silent)
+    }
+
+    /// Writes everything to a new buffer, which can be read back from the
+    /// [`sink`](DebugConfig::sink).
+    pub fn buffer() -> Self {
+        Self::new(DebugSink::buffer(), Verbosity::Receipts)
+    }
+
+    /// Prints everything to stdout.
+    pub fn verbose() -> Self {
+        Self::new(DebugSink::Stdout, Verbosity::Receipts)
+    }
+
+    /// Prints to stdout with the verbosity of the `SIM_DEBUG` env var, which
+    /// is one of `silent`, `commands`, `responses` or `receipts`.
+    ///
+    /// Without the env var, only the commands are printed.
+    pub fn from_env() -> Self {
+        let verbosity = match std::env::var("SIM_DEBUG") {
+            Ok(verbosity) => verbosity
+                .parse()
+                .unwrap_or_else(|err| panic!("invalid SIM_DEBUG: {}", err)),
+            Err(_) => Verbosity::Commands,
+        };
+        Self::new(DebugSink::Stdout, verbosity)
+    }
+}
+
+impl Default for DebugConfig {
+    /// See [`DebugConfig::from_env`].
+    fn default() -> Self {
+        Self::from_env()
+    }
+}
+
+static GLOBAL: Lazy<RwLock<DebugConfig>> = Lazy::new(Default::default);
+
+/// Sets the config used by threads that didn't set their own.
+pub fn set_global(config: DebugConfig) {
+    *GLOBAL.write().unwrap() = config;
+}
+
+/// Sets (or, with `None`, unsets) the config for the current thread,
+/// returning the previous one.
+///
+/// Tests run on their own threads, so this is a per-test config.
+pub fn set_local(config: Option<DebugConfig>) -> Option<DebugConfig> {
+    Scoped::replace_active(config.map(Scoped::new)).map(|previous| previous.lock().clone())
+}
+
+/// The config for the current thread.
+pub fn current() -> DebugConfig {
+    Scoped::<DebugConfig>::with_active(|local| local.clone())
+        .unwrap_or_else(|| GLOBAL.read().unwrap().clone())
+}
+
+/// Runs `f` with the `config` set for the current thread, and then
+/// restores the previous one.
+pub fn with_config<R>(
+    config: DebugConfig,
+    f: impl FnOnce() -> R,
+) -> R {
+    Scoped::new(config).activate(f)
+}
+
+/// Writes a line to the current sink, if the current verbosity is at
+/// least `level`.
+pub fn write_line(
+    level: Verbosity,
+    args: std::fmt::Arguments,
+) {
+    let config = current();
+    if level <= config.verbosity {
+        config.sink.write_line(args);
+    }
+}
+
+/// Same as `println!`, but writes to the current debug sink if it's
+/// verbosity is at least the given level.
+macro_rules! debugln {
+    ($level:expr, $($arg:tt)*) => {
+        $crate::sim::debug::write_line($level, format_args!($($arg)*))
+    };
+}
+pub(crate) use debugln;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verbosity() {
+        let config = DebugConfig::new(DebugSink::buffer(), Verbosity::Responses);
+        with_config(config.clone(), || {
+            debugln!(Verbosity::Commands, "near view a b '{}'", "{}");
+            debugln!(Verbosity::Responses, "response");
+            debugln!(Verbosity::Receipts, "receipts");
+        });
+        assert_eq!(config.sink.take(), "near view a b '{}'\nresponse\n");
+        assert_eq!(config.sink.take(), "");
+
+        // restored after the closure
+        debugln!(Verbosity::Commands, "not captured");
+        assert_eq!(config.sink.take(), "");
+    }
+
+    #[test]
+    fn local() {
+        let config = DebugConfig::buffer();
+        assert!(set_local(Some(config.clone())).is_none());
+        with_config(DebugConfig::silent(), || {
+            debugln!(Verbosity::Commands, "silent");
+        });
+        debugln!(Verbosity::Commands, "local");
+        assert_eq!(config.sink.take(), "local\n");
+        assert!(set_local(None).is_some());
+    }
+
+    #[test]
+    fn parse_verbosity() {
+        assert_eq!("Receipts".parse(), Ok(Verbosity::Receipts));
+        assert_eq!("silent".parse(), Ok(Verbosity::Silent));
+        assert!("all".parse::<Verbosity>().is_err());
+    }
+}
@@ -1,3 +1,6 @@
+use super::debug::{debugln, Verbosity};
+use super::failure::FailureKind;
+use super::receipt_tree::ReceiptNode;
 use near_sdk::Gas;
 use near_sdk_sim::transaction::ExecutionStatus;
 use near_sdk_sim::{ExecutionResult, ViewResult};
@@ -13,6 +16,17 @@ pub trait ExecutionExt {
         &self,
         regex: &str,
     );
+    /// Asserts that the transaction or any receipt that it created failed
+    /// with the `expected` kind of error.
+    ///
+    /// For views, see [`FailureKind::matches_view`].
+    fn assert_failure_kind(
+        &self,
+        expected: FailureKind,
+    );
+    /// Asserts that neither the transaction nor any receipt that it created
+    /// failed.
+    fn assert_no_failures(&self);
     fn total_gas_burnt(&self) -> Gas;
     fn pretty_debug(&self);
     fn all_logs(&self) -> Vec<String>;
@@ -69,6 +83,24 @@ impl ExecutionExt for ExecutionResult {
             }
         }
     }
+    fn assert_failure_kind(
+        &self,
+        expected: FailureKind,
+    ) {
+        let tree = ReceiptNode::new(self);
+        if tree.find_failure(&expected).is_none() {
+            panic!(
+                "expected a failure matching {:?}, but got:\n{}",
+                expected, tree
+            );
+        }
+    }
+    fn assert_no_failures(&self) {
+        let tree = ReceiptNode::new(self);
+        if !tree.failures().is_empty() {
+            panic!("expected no failures, but got:\n{}", tree);
+        }
+    }
     fn total_gas_burnt(&self) -> Gas {
         let res = self
             // .get_receipt_results()
@@ -100,23 +132,8 @@ impl ExecutionExt for ExecutionResult {
         }
         writeln!(f, "--- call stack ---").unwrap();
 
-        for r in self.promise_results().into_iter().flatten() {
-            writeln!(
-                f,
-                "{:?} - status: {:?} - gas burnt: {}",
-                r.executor_id(),
-                pretty_status(r.status()),
-                pretty_gas(r.gas_burnt())
-            )
-            .unwrap();
-
-            let logs = r.logs();
-
-            for l in logs {
-                writeln!(f, "- log: {}", l).unwrap();
-            }
-        }
-        println!("{}", f);
+        write!(f, "{}", ReceiptNode::new(self)).unwrap();
+        debugln!(Verbosity::Receipts, "{}", f);
         // writeln!(f, "--- end of pretty debug ---").unwrap();
     }
     fn all_logs(&self) -> Vec<String> {
@@ -149,6 +166,27 @@ impl ExecutionExt for ViewResult {
         let view_err = self.unwrap_err();
         assert!(regex.is_match(&view_err.to_string()))
     }
+    fn assert_failure_kind(
+        &self,
+        expected: FailureKind,
+    ) {
+        assert!(
+            expected.view_error().is_some(),
+            "a view can't fail with {:?}",
+            expected
+        );
+        assert!(self.is_err(), "expected a failure matching {:?}", expected);
+        let err = self.unwrap_err();
+        assert!(
+            expected.matches_view(err),
+            "expected a failure matching {:?}, but got: {}",
+            expected,
+            err
+        );
+    }
+    fn assert_no_failures(&self) {
+        assert!(self.is_ok(), "{}", self.unwrap_err())
+    }
     fn total_gas_burnt(&self) -> Gas {
         panic!("View method doesn't burn any gas")
     }
@@ -166,7 +204,9 @@ impl ExecutionExt for ViewResult {
 
         // writeln!(f, "---").unwrap();
 
-        print!("{}", f);
+        if !f.is_empty() {
+            debugln!(Verbosity::Receipts, "{}", f.trim_end());
+        }
     }
     fn all_logs(&self) -> Vec<String> {
         self.logs().clone()
@@ -174,33 +214,21 @@ impl ExecutionExt for ViewResult {
 }
 
 pub fn pretty_gas(gas: Gas) -> String {
-    const TERA: u64 = 1_000_000_000_000;
-    let tgas = gas.0 / TERA;
-    let rem = gas.0 % TERA;
-    if rem == 0 {
-        format!("{} TGas", tgas)
-    } else {
-        // ignore whats below milli_tera
-        let milli_tera = TERA / 1000;
-        let milli_tera_gas = rem / milli_tera;
-
-        format!("~{}.{:0>3} TGas", tgas, milli_tera_gas)
-    }
+    crate::gas::format_gas(gas)
 }
 
+/// Formats a yoctoNEAR amount in it's most readable unit, with at most
+/// three decimals.
+///
+/// See [`crate::near_amount::NearFormat`] for other options.
 pub fn pretty_near(near: u128) -> String {
-    const TERA: u128 = 1_000_000_000_000;
-    let tnear = near / TERA;
-    let rem = near % TERA;
-    if rem == 0 {
-        format!("{} TNear", tnear)
-    } else {
-        // ignore whats below milli_tera
-        let milli_tera = TERA / 1000;
-        let milli_tera_near = rem / milli_tera;
-
-        format!("~{}.{:0>3} TNear", tnear, milli_tera_near)
-    }
+    use crate::near_amount::{NearFormat, Rounding};
+
+    NearFormat::new()
+        .adaptive()
+        .round(3, Rounding::HalfEven)
+        .trim_zeros()
+        .format(near)
 }
 
 pub fn pretty_status(status: ExecutionStatus) -> String {
@@ -0,0 +1,233 @@
+use near_sdk::AccountId;
+use near_sdk_sim::errors::{ActionErrorKind, InvalidTxError, TxExecutionError};
+use near_vm_errors::{FunctionCallError, HostError, MethodResolveError, VMError, VMLogicError};
+
+/// A kind of failure expected from a transaction or receipt, matched against
+/// it's structured [`TxExecutionError`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FailureKind {
+    /// The contract panicked with this message, such as from `require!` or
+    /// `env::panic_str`.
+    GuestPanic(String),
+    /// The called method doesn't exist on the contract.
+    MethodNotFound,
+    /// The call used all of the gas that was attached to it.
+    ExceededPrepaidGas,
+    /// The signer can't pay for the transaction.
+    ///
+    /// The runtime of [`near_sdk_sim::init_simulator`] panics on invalid
+    /// transactions, so this only matches on a runtime created by
+    /// [`sim::init_simulator`](super::init_simulator).
+    NotEnoughBalance,
+    /// The account wouldn't have enough balance for it's storage.
+    LackBalanceForState,
+    /// The receiver account doesn't exist.
+    AccountDoesNotExist(AccountId),
+    /// The account to create already exists.
+    AccountAlreadyExists(AccountId),
+    /// Exactly this error.
+    Exact(TxExecutionError),
+}
+
+impl FailureKind {
+    /// The host error of this kind, if it's one.
+    fn host_error(&self) -> Option<HostError> {
+        match self {
+            Self::GuestPanic(msg) => Some(HostError::GuestPanic {
+                panic_msg: msg.clone(),
+            }),
+            Self::ExceededPrepaidGas => Some(HostError::GasExceeded),
+            _ => None,
+        }
+    }
+
+    pub fn matches(
+        &self,
+        err: &TxExecutionError,
+    ) -> bool {
+        let kind = match err {
+            TxExecutionError::ActionError(e) => Some(&e.kind),
+            TxExecutionError::InvalidTxError(_) => None,
+        };
+        let function_call = match kind {
+            Some(ActionErrorKind::FunctionCallError(e)) => Some(e),
+            _ => None,
+        };
+
+        match self {
+            Self::GuestPanic(_) | Self::ExceededPrepaidGas => {
+                let expected = self.host_error().unwrap();
+                matches!(function_call, Some(FunctionCallError::HostError(e)) if *e == expected)
+            }
+            Self::MethodNotFound => matches!(
+                function_call,
+                Some(FunctionCallError::MethodResolveError(
+                    MethodResolveError::MethodNotFound
+                ))
+            ),
+            Self::NotEnoughBalance => matches!(
+                err,
+                TxExecutionError::InvalidTxError(InvalidTxError::NotEnoughBalance { .. })
+            ),
+            Self::LackBalanceForState => {
+                matches!(kind, Some(ActionErrorKind::LackBalanceForState { .. }))
+                    || matches!(
+                        err,
+                        TxExecutionError::InvalidTxError(
+                            InvalidTxError::LackBalanceForState { .. }
+                        )
+                    )
+            }
+            Self::AccountDoesNotExist(expected) => match kind {
+                Some(ActionErrorKind::AccountDoesNotExist { account_id }) => {
+                    account_id == expected.as_str()
+                }
+                _ => false,
+            },
+            Self::AccountAlreadyExists(expected) => match kind {
+                Some(ActionErrorKind::AccountAlreadyExists { account_id }) => {
+                    account_id == expected.as_str()
+                }
+                _ => false,
+            },
+            Self::Exact(expected) => err == expected,
+        }
+    }
+
+    /// Whether the error of a failed view call is of this kind.
+    ///
+    /// The error is matched on the [`VMLogicError`] that caused it, if it's
+    /// in it's [`source`](std::error::Error::source) chain. The simulator's
+    /// runtime only keeps the text of the error though, so otherwise the
+    /// text is matched against the [`view_error`](Self::view_error).
+    pub fn matches_view(
+        &self,
+        err: &dyn std::error::Error,
+    ) -> bool {
+        let mut source = err.source();
+        while let Some(err) = source {
+            if let Some(VMLogicError::HostError(host_error)) = err.downcast_ref::<VMLogicError>() {
+                return self.host_error().as_ref() == Some(host_error);
+            }
+            source = err.source();
+        }
+        self.view_error()
+            .map_or(false, |expected| err.to_string() == expected)
+    }
+
+    /// The error of a view call that failed with this kind of failure.
+    ///
+    /// View calls only report their errors as text, so this is the text of
+    /// the structured error, as the runtime formats it. Only guest panics,
+    /// missing methods and exceeded gas can be the failure of a view call.
+    pub fn view_error(&self) -> Option<String> {
+        let err = match (self, self.host_error()) {
+            (_, Some(host_error)) => FunctionCallError::HostError(host_error),
+            (Self::MethodNotFound, None) => {
+                FunctionCallError::MethodResolveError(MethodResolveError::MethodNotFound)
+            }
+            _ => return None,
+        };
+        Some(format!(
+            "wasm execution failed with error: {:?}",
+            VMError::FunctionCallError(err)
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk_sim::errors::ActionError;
+
+    #[test]
+    fn matches() {
+        let err = TxExecutionError::ActionError(ActionError {
+            index: Some(0),
+            kind: ActionErrorKind::AccountDoesNotExist {
+                account_id: "nobody".parse().unwrap(),
+            },
+        });
+        assert!(FailureKind::AccountDoesNotExist("nobody".parse().unwrap()).matches(&err));
+        assert!(!FailureKind::AccountDoesNotExist("alice".parse().unwrap()).matches(&err));
+        assert!(FailureKind::Exact(err.clone()).matches(&err));
+        assert!(!FailureKind::GuestPanic("nobody".into()).matches(&err));
+        assert!(!FailureKind::MethodNotFound.matches(&err));
+        assert!(!FailureKind::NotEnoughBalance.matches(&err));
+    }
+
+    #[test]
+    fn function_call() {
+        let err = |e| {
+            TxExecutionError::ActionError(ActionError {
+                index: Some(0),
+                kind: ActionErrorKind::FunctionCallError(e),
+            })
+        };
+
+        let panic = FailureKind::GuestPanic("oops".into());
+        let guest_panic = |msg: &str| {
+            err(FunctionCallError::HostError(HostError::GuestPanic {
+                panic_msg: msg.into(),
+            }))
+        };
+        assert!(panic.matches(&guest_panic("oops")));
+        assert!(!panic.matches(&guest_panic("other")));
+
+        let gas = FailureKind::ExceededPrepaidGas;
+        assert!(gas.matches(&err(FunctionCallError::HostError(HostError::GasExceeded))));
+        assert!(!gas.matches(&guest_panic("oops")));
+
+        let method = err(FunctionCallError::MethodResolveError(
+            MethodResolveError::MethodNotFound,
+        ));
+        assert!(FailureKind::MethodNotFound.matches(&method));
+        assert!(!panic.matches(&method));
+    }
+
+    #[test]
+    fn view_error() {
+        assert_eq!(
+            FailureKind::GuestPanic("oops".into()).view_error().unwrap(),
+            r#"wasm execution failed with error: FunctionCallError(HostError(GuestPanic { panic_msg: "oops" }))"#
+        );
+        assert_eq!(
+            FailureKind::MethodNotFound.view_error().unwrap(),
+            "wasm execution failed with error: FunctionCallError(MethodResolveError(MethodNotFound))"
+        );
+        assert_eq!(FailureKind::NotEnoughBalance.view_error(), None);
+    }
+
+    #[test]
+    fn matches_view() {
+        /// A view error caused by the `source`.
+        #[derive(Debug)]
+        struct ViewError(VMLogicError);
+        impl std::fmt::Display for ViewError {
+            fn fmt(
+                &self,
+                f: &mut std::fmt::Formatter<'_>,
+            ) -> std::fmt::Result {
+                f.write_str("view failed")
+            }
+        }
+        impl std::error::Error for ViewError {
+            fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+                Some(&self.0)
+            }
+        }
+
+        let panic = FailureKind::GuestPanic("oops".into());
+        let err = ViewError(VMLogicError::HostError(HostError::GuestPanic {
+            panic_msg: "oops".into(),
+        }));
+        assert!(panic.matches_view(&err));
+        assert!(!FailureKind::GuestPanic("other".into()).matches_view(&err));
+        assert!(!FailureKind::ExceededPrepaidGas.matches_view(&err));
+
+        // only the text is known
+        let text: Box<dyn std::error::Error> = panic.view_error().unwrap().into();
+        assert!(panic.matches_view(text.as_ref()));
+        assert!(!FailureKind::MethodNotFound.matches_view(text.as_ref()));
+    }
+}
@@ -0,0 +1,453 @@
+//! Declarative setup of a simulated environment, with it's accounts,
+//! contracts and setup calls.
+//!
+//! ```ignore
+//! let mut builder = Fixture::builder()
+//!     .users(2, to_yocto("100"))
+//!     .long_user("alice", to_yocto("100"));
+//! let nft = builder.contract::<NftContract>("nft", &NFT_WASM, "new", json!({}), 0);
+//! let fixture = builder
+//!     .call(user(0), &nft, "storage_deposit", json!({}), to_yocto("1"))
+//!     .build();
+//!
+//! let nft = fixture.get(&nft);
+//! let alice = fixture.long_user("alice");
+//! ```
+
+use super::contract_ext::{Arbitrary, ContractAcc, ContractExt};
+use super::{runtime, transcript};
+use near_sdk::utils::WithAccount;
+use near_sdk::{serde_json, AccountId, Balance, Gas};
+use near_sdk_sim::{ContractAccount, UserAccount};
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+
+/// Parses an account id of the fixture.
+fn parse_account_id(account_id: &str) -> AccountId {
+    account_id
+        .parse()
+        .unwrap_or_else(|_| panic!("the account id {} is invalid", account_id))
+}
+
+/// Refers to a contract of a [`FixtureBuilder`], which is deployed with the
+/// type `T`, to get it from the built [`Fixture`].
+pub struct ContractHandle<T> {
+    account_id: AccountId,
+    _contract: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for ContractHandle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            account_id: self.account_id.clone(),
+            _contract: PhantomData,
+        }
+    }
+}
+
+impl<T> ContractHandle<T> {
+    pub fn account_id(&self) -> &AccountId {
+        &self.account_id
+    }
+}
+
+impl<T> AsRef<str> for ContractHandle<T> {
+    fn as_ref(&self) -> &str {
+        self.account_id.as_str()
+    }
+}
+
+/// A contract of a [`Fixture`], like a [`ContractAccount`] that borrows it's
+/// account from the fixture.
+pub struct FixtureContract<'fixture, T> {
+    pub user_account: &'fixture UserAccount,
+    pub contract: T,
+}
+
+impl<T> ContractAcc for FixtureContract<'_, T> {
+    type Contract = T;
+    fn account_id(&self) -> AccountId {
+        self.user_account.account_id()
+    }
+    fn account(&self) -> Option<near_sdk_sim::account::Account> {
+        self.user_account.account()
+    }
+    fn user_account(&self) -> &UserAccount {
+        self.user_account
+    }
+}
+
+type DeployFn = Box<dyn FnOnce(&UserAccount, Gas) -> UserAccount>;
+
+struct ContractSpec {
+    account_id: AccountId,
+    deploy: DeployFn,
+}
+
+struct SetupCall {
+    signer_id: AccountId,
+    contract_id: AccountId,
+    method: String,
+    args: serde_json::Value,
+    deposit: Balance,
+}
+
+/// Lists the accounts, contracts and setup calls of a [`Fixture`].
+///
+/// On [`build`](FixtureBuilder::build), the accounts are created first,
+/// then the contracts are deployed and then the setup calls are made, each
+/// in the order that they were added.
+pub struct FixtureBuilder {
+    gas: Gas,
+    accounts: Vec<(AccountId, Balance)>,
+    contracts: Vec<ContractSpec>,
+    calls: Vec<SetupCall>,
+}
+
+impl Default for FixtureBuilder {
+    fn default() -> Self {
+        Self {
+            gas: Gas(near_sdk_sim::DEFAULT_GAS),
+            accounts: vec![],
+            contracts: vec![],
+            calls: vec![],
+        }
+    }
+}
+
+impl FixtureBuilder {
+    /// Gas attached to the deploys and setup calls.
+    pub fn gas(
+        mut self,
+        gas: Gas,
+    ) -> Self {
+        self.gas = gas;
+        self
+    }
+
+    /// Adds an account, created by the root account.
+    pub fn account(
+        mut self,
+        account_id: impl AsRef<str>,
+        balance: Balance,
+    ) -> Self {
+        let account_id = parse_account_id(account_id.as_ref());
+        self.accounts.push((account_id, balance));
+        self
+    }
+
+    /// Adds the accounts from [`user(0)`](super::user) up to `user(n - 1)`.
+    pub fn users(
+        mut self,
+        n: u32,
+        balance: Balance,
+    ) -> Self {
+        for id in 0..n {
+            self = self.account(super::user(id), balance);
+        }
+        self
+    }
+
+    /// Adds the account [`long_user(s)`](super::long_user).
+    pub fn long_user(
+        self,
+        s: &str,
+        balance: Balance,
+    ) -> Self {
+        self.account(super::long_user(s), balance)
+    }
+
+    /// Adds a contract, which is deployed by the root account and
+    /// initialized with `method` and `args`, with the `deposit` attached.
+    ///
+    /// The returned handle gets the contract from the built fixture, with
+    /// [`Fixture::get`].
+    pub fn contract<T>(
+        &mut self,
+        contract_id: impl AsRef<str>,
+        wasm_bytes: &[u8],
+        method: &str,
+        args: serde_json::Value,
+        deposit: Balance,
+    ) -> ContractHandle<T>
+    where
+        T: WithAccount,
+    {
+        let account_id = parse_account_id(contract_id.as_ref());
+        let contract_id = account_id.to_string();
+        let wasm_bytes = wasm_bytes.to_vec();
+        let method = method.to_string();
+        self.contracts.push(ContractSpec {
+            account_id: account_id.clone(),
+            deploy: Box::new(move |root, gas| {
+                ContractAccount::<T>::json_deploy(
+                    root,
+                    &contract_id,
+                    &wasm_bytes,
+                    &method,
+                    args,
+                    gas,
+                    deposit,
+                )
+                .user_account
+            }),
+        });
+        ContractHandle {
+            account_id,
+            _contract: PhantomData,
+        }
+    }
+
+    /// Adds a call from `signer_id` to the `contract_id` contract, such as
+    /// for registering storage. The call must succeed.
+    ///
+    /// The signer can be the root account, or any account or contract of
+    /// the fixture.
+    pub fn call(
+        mut self,
+        signer_id: impl AsRef<str>,
+        contract_id: impl AsRef<str>,
+        method: &str,
+        args: serde_json::Value,
+        deposit: Balance,
+    ) -> Self {
+        self.calls.push(SetupCall {
+            signer_id: parse_account_id(signer_id.as_ref()),
+            contract_id: parse_account_id(contract_id.as_ref()),
+            method: method.to_string(),
+            args,
+            deposit,
+        });
+        self
+    }
+
+    /// Initializes the simulator and sets up the fixture in it.
+    ///
+    /// The simulator keeps the receipts (see [`runtime`]).
+    pub fn build(self) -> Fixture {
+        let root = runtime::init_simulator(None);
+        let mut fixture = Fixture {
+            root,
+            accounts: BTreeMap::new(),
+            contracts: BTreeMap::new(),
+        };
+
+        for (account_id, balance) in self.accounts {
+            assert!(
+                !fixture.accounts.contains_key(&account_id),
+                "the account {} was added more than once",
+                account_id
+            );
+            let account = transcript::create_user(&fixture.root, account_id.clone(), balance);
+            fixture.accounts.insert(account_id, account);
+        }
+
+        for spec in self.contracts {
+            assert!(
+                !fixture.accounts.contains_key(&spec.account_id)
+                    && !fixture.contracts.contains_key(&spec.account_id),
+                "the contract {} was added more than once",
+                spec.account_id
+            );
+            let contract = (spec.deploy)(&fixture.root, self.gas);
+            fixture.contracts.insert(spec.account_id, contract);
+        }
+
+        for call in self.calls {
+            let signer = fixture.account(&call.signer_id);
+            let contract = FixtureContract {
+                user_account: fixture.contract_account(&call.contract_id),
+                contract: (),
+            };
+            let tree = contract
+                .json_call::<Arbitrary>(signer, &call.method, call.args, self.gas, call.deposit)
+                .receipt_tree();
+            if !tree.failures().is_empty() {
+                panic!(
+                    "the setup call {}::{} by {} failed:\n{}",
+                    call.contract_id, call.method, call.signer_id, tree
+                );
+            }
+        }
+
+        fixture
+    }
+}
+
+/// Handle to the accounts and contracts of a simulated environment, set up
+/// by a [`FixtureBuilder`].
+pub struct Fixture {
+    pub root: UserAccount,
+    accounts: BTreeMap<AccountId, UserAccount>,
+    contracts: BTreeMap<AccountId, UserAccount>,
+}
+
+impl Fixture {
+    pub fn builder() -> FixtureBuilder {
+        FixtureBuilder::default()
+    }
+
+    /// The root account, or any account or contract of the fixture.
+    pub fn account(
+        &self,
+        account_id: impl AsRef<str>,
+    ) -> &UserAccount {
+        let account_id = parse_account_id(account_id.as_ref());
+        if account_id == self.root.account_id() {
+            return &self.root;
+        }
+        if let Some(account) = self.accounts.get(&account_id) {
+            return account;
+        }
+        self.contracts
+            .get(&account_id)
+            .unwrap_or_else(|| panic!("the account {} is not in the fixture", account_id))
+    }
+
+    /// The account [`user(id)`](super::user).
+    pub fn user(
+        &self,
+        id: u32,
+    ) -> &UserAccount {
+        self.account(super::user(id))
+    }
+
+    /// The account [`long_user(s)`](super::long_user).
+    pub fn long_user(
+        &self,
+        s: &str,
+    ) -> &UserAccount {
+        self.account(super::long_user(s))
+    }
+
+    /// The account of the contract deployed as `contract_id`.
+    fn contract_account(
+        &self,
+        contract_id: &AccountId,
+    ) -> &UserAccount {
+        self.contracts
+            .get(contract_id)
+            .unwrap_or_else(|| panic!("the contract {} is not in the fixture", contract_id))
+    }
+
+    /// The contract of the `handle`, with it's type.
+    pub fn get<T>(
+        &self,
+        handle: &ContractHandle<T>,
+    ) -> FixtureContract<'_, T>
+    where
+        T: WithAccount,
+    {
+        FixtureContract {
+            user_account: self.contract_account(&handle.account_id),
+            contract: T::with_account(handle.account_id.clone()),
+        }
+    }
+
+    /// Ids of the accounts and contracts of the fixture, besides the root.
+    pub fn account_ids(&self) -> Vec<AccountId> {
+        self.accounts
+            .keys()
+            .chain(self.contracts.keys())
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gas::TGAS;
+    use crate::sim::contract_ext::{DummyContract, TEST_CONTRACT_WASM};
+    use crate::sim::{long_user, user};
+    use near_sdk::serde_json::json;
+    use near_sdk_sim::to_yocto;
+
+    #[test]
+    fn accounts() {
+        let fixture = Fixture::builder()
+            .users(2, to_yocto("10"))
+            .long_user("alice", to_yocto("20"))
+            .account("bob", to_yocto("30"))
+            .build();
+
+        assert_eq!(
+            fixture.account_ids(),
+            ["bob".parse().unwrap(), long_user("alice"), user(0), user(1)]
+        );
+        assert_eq!(fixture.user(1).account_id(), user(1));
+        assert_eq!(fixture.user(1).account().unwrap().amount, to_yocto("10"));
+        assert_eq!(
+            fixture.long_user("alice").account().unwrap().amount,
+            to_yocto("20")
+        );
+        assert_eq!(
+            fixture.account("bob").account().unwrap().amount,
+            to_yocto("30")
+        );
+        assert_eq!(
+            fixture.account("root").account_id(),
+            fixture.root.account_id()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "the account user0 was added more than once")]
+    fn duplicated() {
+        Fixture::builder()
+            .users(1, to_yocto("10"))
+            .account("user0", to_yocto("10"))
+            .build();
+    }
+
+    #[test]
+    #[should_panic(expected = "the contract nft is not in the fixture")]
+    fn unknown_contract() {
+        Fixture::builder()
+            .users(1, to_yocto("10"))
+            .call(user(0), "nft", "storage_deposit", json!({}), 0)
+            .build();
+    }
+
+    fn with_test_contract(method: &str) -> (Fixture, ContractHandle<DummyContract>) {
+        let mut builder = Fixture::builder().users(1, to_yocto("10"));
+        let handle = builder.contract::<DummyContract>(
+            "contract",
+            TEST_CONTRACT_WASM,
+            "new",
+            json!({}),
+            to_yocto("10"),
+        );
+        let fixture = builder
+            .call(user(0), &handle, method, json!("hello"), 0)
+            .build();
+        (fixture, handle)
+    }
+
+    #[test]
+    fn contract() {
+        let (fixture, handle) = with_test_contract("write");
+        let contract = fixture.get(&handle);
+        assert_eq!(contract.account_id(), *handle.account_id());
+        assert_eq!(contract.contract.account_id, *handle.account_id());
+
+        let before = contract.account().unwrap().storage_usage;
+        contract
+            .json_call::<()>(fixture.user(0), "remove", json!({}), (10 * TGAS).into(), 0)
+            .assert_success();
+        // the setup call had written to the storage
+        assert!(contract.account().unwrap().storage_usage < before);
+    }
+
+    #[test]
+    #[should_panic(expected = "the setup call contract::fail_always by user0 failed")]
+    fn failed_setup_call() {
+        with_test_contract("fail_always");
+    }
+
+    #[test]
+    #[should_panic(expected = "the account id not valid! is invalid")]
+    fn invalid_account_id() {
+        Fixture::builder().account("not valid!", to_yocto("10"));
+    }
+}
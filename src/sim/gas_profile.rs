@@ -0,0 +1,358 @@
+//! Aggregates the gas burnt by the calls made through
+//! [`ContractExt`](super::ContractExt) and [`Batch`](super::Batch), per
+//! contract method and per receipt executor, into a report that can be
+//! compared against a baseline.
+
+use super::receipt_tree::ReceiptNode;
+use super::scoped::Scoped;
+use crate::gas::format_gas;
+use crate::js_integer::JsU128;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{serde_json, AccountId, Gas};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Statistics of the gas burnt by some method or receipt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct GasStats {
+    pub count: u64,
+    pub min: u64,
+    pub max: u64,
+    pub total: JsU128,
+}
+
+impl GasStats {
+    pub fn new(gas: Gas) -> Self {
+        Self {
+            count: 1,
+            min: gas.0,
+            max: gas.0,
+            total: JsU128::new(gas.0 as u128),
+        }
+    }
+
+    pub fn add(
+        &mut self,
+        gas: Gas,
+    ) {
+        self.count += 1;
+        self.min = self.min.min(gas.0);
+        self.max = self.max.max(gas.0);
+        self.total = JsU128::new(self.total.get() + gas.0 as u128);
+    }
+
+    pub fn mean(&self) -> Gas {
+        Gas((self.total.get() / self.count as u128) as u64)
+    }
+}
+
+/// Gas burnt per contract method (as `contract::method`), by the whole
+/// call, and per receipt executor.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct GasReport {
+    pub methods: BTreeMap<String, GasStats>,
+    pub receipts: BTreeMap<String, GasStats>,
+}
+
+/// A method which mean gas burnt increased over it's baseline by more than
+/// the allowed threshold.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GasRegression {
+    pub method: String,
+    pub baseline: Gas,
+    pub current: Gas,
+}
+
+impl std::fmt::Display for GasRegression {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        let increase = self.current.0.saturating_sub(self.baseline.0) as u128 * 100
+            / (self.baseline.0 as u128).max(1);
+        write!(
+            f,
+            "{}: {} -> {} (+{}%)",
+            self.method,
+            format_gas(self.baseline),
+            format_gas(self.current),
+            increase
+        )
+    }
+}
+
+fn add_stats(
+    map: &mut BTreeMap<String, GasStats>,
+    key: String,
+    gas: Gas,
+) {
+    map.entry(key)
+        .and_modify(|stats| stats.add(gas))
+        .or_insert_with(|| GasStats::new(gas));
+}
+
+impl GasReport {
+    /// Adds a call to `method` on `contract_id`, which created the `tree`.
+    pub fn add_call(
+        &mut self,
+        contract_id: &AccountId,
+        method: &str,
+        tree: &ReceiptNode,
+    ) {
+        let key = format!("{}::{}", contract_id, method);
+        add_stats(&mut self.methods, key, tree.total_gas_burnt());
+        for (_depth, node) in tree.iter() {
+            add_stats(
+                &mut self.receipts,
+                node.executor_id.to_string(),
+                node.gas_burnt,
+            );
+        }
+    }
+
+    /// Renders the report as a table with the count, min, mean and max of
+    /// each method and receipt executor.
+    pub fn to_table(&self) -> String {
+        use std::fmt::Write;
+
+        let rows = [("method", &self.methods), ("receipt", &self.receipts)];
+        let width = rows
+            .iter()
+            .flat_map(|(_kind, map)| map.keys())
+            .map(|name| name.chars().count())
+            .max()
+            .unwrap_or(0)
+            .max("receipt".len());
+
+        let mut f = String::new();
+        for (kind, map) in rows {
+            if map.is_empty() {
+                continue;
+            }
+            writeln!(
+                f,
+                "{:<width$} {:>6} {:>16} {:>16} {:>16}",
+                kind,
+                "count",
+                "min",
+                "mean",
+                "max",
+                width = width
+            )
+            .unwrap();
+            for (name, stats) in map {
+                writeln!(
+                    f,
+                    "{:<width$} {:>6} {:>16} {:>16} {:>16}",
+                    name,
+                    stats.count,
+                    format_gas(Gas(stats.min)),
+                    format_gas(stats.mean()),
+                    format_gas(Gas(stats.max)),
+                    width = width
+                )
+                .unwrap();
+            }
+        }
+        f
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap()
+    }
+
+    pub fn from_json(value: serde_json::Value) -> Result<Self, serde_json::Error> {
+        serde_json::from_value(value)
+    }
+
+    /// Writes the report as JSON, such as for a baseline.
+    pub fn write(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
+        std::fs::write(path, json)
+    }
+
+    /// Reads a report written by [`GasReport::write`].
+    pub fn read(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let json = std::fs::read(path)?;
+        serde_json::from_slice(&json)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+    }
+
+    /// Methods which mean gas burnt increased by more than
+    /// `max_increase_percent` over the `baseline`.
+    ///
+    /// Methods that are not in the baseline are ignored.
+    pub fn regressions(
+        &self,
+        baseline: &GasReport,
+        max_increase_percent: u64,
+    ) -> Vec<GasRegression> {
+        self.methods
+            .iter()
+            .filter_map(|(method, stats)| {
+                let baseline = baseline.methods.get(method)?.mean();
+                let current = stats.mean();
+                let limit = baseline.0 as u128 * (100 + max_increase_percent as u128);
+                if current.0 as u128 * 100 > limit {
+                    Some(GasRegression {
+                        method: method.clone(),
+                        baseline,
+                        current,
+                    })
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Asserts that no method regressed, as in [`GasReport::regressions`].
+    pub fn assert_no_regressions(
+        &self,
+        baseline: &GasReport,
+        max_increase_percent: u64,
+    ) {
+        let regressions = self.regressions(baseline, max_increase_percent);
+        if !regressions.is_empty() {
+            let regressions: Vec<String> = regressions.iter().map(ToString::to_string).collect();
+            panic!(
+                "gas usage increased by more than {}%:\n{}",
+                max_increase_percent,
+                regressions.join("\n")
+            );
+        }
+    }
+}
+
+/// Collects the gas burnt by calls while it's active.
+///
+/// Clones share the same report, so it can be shared between tests, such
+/// as from a `static`.
+#[derive(Debug, Clone, Default)]
+pub struct GasProfiler {
+    report: Scoped<GasReport>,
+}
+
+impl GasProfiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Profiles the calls that the current thread makes while `f` runs.
+    pub fn profile<R>(
+        &self,
+        f: impl FnOnce() -> R,
+    ) -> R {
+        self.report.activate(f)
+    }
+
+    pub fn report(&self) -> GasReport {
+        self.report.lock().clone()
+    }
+}
+
+/// Adds the call into the current thread's active profiler, if any.
+pub(crate) fn record_call(
+    contract_id: &AccountId,
+    method: &str,
+    tree: impl FnOnce() -> ReceiptNode,
+) {
+    Scoped::<GasReport>::with_active(|report| report.add_call(contract_id, method, &tree()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gas::TGAS;
+    use crate::sim::contract_ext::{ContractExt, DummyContract};
+    use near_sdk::serde_json::json;
+    use near_sdk::utils::WithAccount;
+    use near_sdk_sim::{init_simulator, to_yocto, ContractAccount};
+
+    fn report(gas: &[u64]) -> GasReport {
+        let mut stats = GasStats::new(Gas(gas[0] * TGAS));
+        for g in &gas[1..] {
+            stats.add(Gas(g * TGAS));
+        }
+        let mut report = GasReport::default();
+        report.methods.insert("nft::mint".into(), stats);
+        report
+    }
+
+    #[test]
+    fn stats() {
+        let report = report(&[4, 2, 6]);
+        let stats = report.methods["nft::mint"];
+        assert_eq!(stats.count, 3);
+        assert_eq!(stats.min, 2 * TGAS);
+        assert_eq!(stats.max, 6 * TGAS);
+        assert_eq!(stats.mean(), Gas(4 * TGAS));
+
+        let json = report.to_json();
+        assert_eq!(json["methods"]["nft::mint"]["total"], 12000000000000u64);
+        assert_eq!(GasReport::from_json(json).unwrap(), report);
+
+        let table = report.to_table();
+        assert_eq!(table.lines().count(), 2);
+        assert!(table
+            .lines()
+            .nth(1)
+            .unwrap()
+            .starts_with("nft::mint      3"));
+    }
+
+    #[test]
+    fn regressions() {
+        let baseline = report(&[10]);
+        assert!(report(&[11]).regressions(&baseline, 10).is_empty());
+        assert_eq!(
+            report(&[12]).regressions(&baseline, 10),
+            [GasRegression {
+                method: "nft::mint".into(),
+                baseline: Gas(10 * TGAS),
+                current: Gas(12 * TGAS),
+            }]
+        );
+        // unknown methods are ignored
+        assert!(report(&[12])
+            .regressions(&GasReport::default(), 10)
+            .is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "nft::mint: 10 TGas -> 12 TGas (+20%)")]
+    fn assert_regressions() {
+        report(&[12]).assert_no_regressions(&report(&[10]), 10);
+    }
+
+    #[test]
+    fn profile() {
+        let root = init_simulator(None);
+        let alice = root.create_user("alice".parse().unwrap(), to_yocto("10"));
+        let bob_id: AccountId = "bob".parse().unwrap();
+        let bob = ContractAccount {
+            user_account: root.create_user(bob_id.clone(), to_yocto("10")),
+            contract: DummyContract::with_account(bob_id),
+        };
+
+        let profiler = GasProfiler::new();
+        let gas = Gas(near_sdk_sim::DEFAULT_GAS);
+        profiler.profile(|| {
+            for _ in 0..2 {
+                // bob has no contract, but the call still burns gas
+                let _ = bob.json_call::<()>(&alice, "method", json!({}), gas, 0);
+            }
+        });
+
+        let report = profiler.report();
+        assert_eq!(report.methods["bob::method"].count, 2);
+        assert_eq!(report.receipts["bob"].count, 2);
+        assert!(report.receipts["alice"].count >= 2);
+    }
+}
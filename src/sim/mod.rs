@@ -1,10 +1,34 @@
 use near_sdk::{AccountId, Gas};
 
+pub mod balances;
+pub mod batch;
 pub mod contract_ext;
+pub mod costs;
+pub mod debug;
 pub mod execution_ext;
+pub mod failure;
+pub mod fixture;
+pub mod gas_profile;
+pub mod receipt_tree;
+pub mod runtime;
+pub mod scoped;
+pub mod storage;
+pub mod transcript;
 
+pub use balances::{track_balances, BalanceDiff, BalanceDiffs};
+pub use batch::{ActionOutcome, Batch, BatchAction, BatchExecution, BatchExt};
 pub use contract_ext::ContractExt;
+pub use costs::RuntimeCosts;
+pub use debug::{DebugConfig, DebugSink, Verbosity};
 pub use execution_ext::{pretty_near, ExecutionExt};
+pub use failure::FailureKind;
+pub use fixture::{ContractHandle, Fixture, FixtureBuilder, FixtureContract};
+pub use gas_profile::{GasProfiler, GasReport};
+pub use receipt_tree::ReceiptNode;
+pub use runtime::init_simulator;
+pub use scoped::Scoped;
+pub use storage::{StorageRecord, StorageTracker};
+pub use transcript::{Recorder, TranscriptEntry};
 
 pub trait IntoGas {
     fn into_gas(self) -> Gas;
@@ -0,0 +1,433 @@
+use super::execution_ext::{pretty_gas, pretty_near, pretty_status, pretty_utf8};
+use super::failure::FailureKind;
+use super::runtime::Receipts;
+use near_sdk::serde_json::{self, json};
+use near_sdk::{AccountId, Balance, Gas};
+use near_sdk_sim::errors::TxExecutionError;
+use near_sdk_sim::hash::CryptoHash;
+use near_sdk_sim::transaction::{Action, ExecutionStatus};
+use near_sdk_sim::ExecutionResult;
+
+/// An executed transaction or receipt, together with the receipts that it
+/// created.
+///
+/// The simulator doesn't keep the receipts themselves, so their predecessors
+/// and actions are only known for the transactions submitted through
+/// [`ContractExt`](super::ContractExt) and [`Batch`](super::Batch), on a
+/// runtime created by [`runtime::init_simulator`](super::runtime::init_simulator).
+/// Otherwise, such as with [`near_sdk_sim::init_simulator`], only the
+/// transaction's own receipt is known, so [`find_call`](Self::find_call),
+/// [`is_refund`](Self::is_refund) and [`deposit`](Self::deposit) don't see
+/// the receipts that it created.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReceiptNode {
+    pub executor_id: AccountId,
+    /// The account that created this receipt, if it's known.
+    ///
+    /// This is `None` for transactions, and `system` for refunds.
+    pub predecessor_id: Option<AccountId>,
+    /// The actions of this receipt, if they are known.
+    ///
+    /// This is empty for transactions, as their actions are in their receipt.
+    pub actions: Vec<Action>,
+    pub status: ExecutionStatus,
+    pub gas_burnt: Gas,
+    pub tokens_burnt: Balance,
+    pub logs: Vec<String>,
+    pub children: Vec<ReceiptNode>,
+}
+
+impl ReceiptNode {
+    /// Builds the tree of the `result` and of all receipts that it created,
+    /// recursively, without their predecessors and actions.
+    pub fn new(result: &ExecutionResult) -> Self {
+        Self::with_receipts(result, &Receipts::new())
+    }
+
+    /// Builds the tree of the `result` and of all receipts that it created,
+    /// recursively, taking each receipt's predecessor and actions from
+    /// `receipts`.
+    pub fn with_receipts(
+        result: &ExecutionResult,
+        receipts: &Receipts,
+    ) -> Self {
+        Self::build(result, None, receipts)
+    }
+
+    fn build(
+        result: &ExecutionResult,
+        receipt_id: Option<&CryptoHash>,
+        receipts: &Receipts,
+    ) -> Self {
+        let receipt_ids = &result.outcome().receipt_ids;
+        let receipt = receipt_id.and_then(|receipt_id| receipts.get(receipt_id));
+        Self {
+            executor_id: result.executor_id().parse().unwrap(),
+            predecessor_id: receipt.map(|receipt| receipt.predecessor_id.clone()),
+            actions: receipt
+                .map(|receipt| receipt.actions.clone())
+                .unwrap_or_default(),
+            status: result.status(),
+            gas_burnt: result.gas_burnt(),
+            tokens_burnt: result.tokens_burnt(),
+            logs: result.logs().clone(),
+            children: receipt_ids
+                .iter()
+                .zip(result.get_receipt_results())
+                .filter_map(|(receipt_id, child)| {
+                    child.map(|child| Self::build(&child, Some(receipt_id), receipts))
+                })
+                .collect(),
+        }
+    }
+
+    /// The methods that this receipt calls.
+    pub fn method_names(&self) -> Vec<&str> {
+        self.actions
+            .iter()
+            .filter_map(|action| match action {
+                Action::FunctionCall(call) => Some(call.method_name.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Whether this receipt refunds some deposit or prepaid gas.
+    pub fn is_refund(&self) -> bool {
+        self.predecessor_id
+            .as_ref()
+            .map_or(false, |predecessor_id| predecessor_id.as_str() == "system")
+    }
+
+    /// The tokens attached to this receipt, by it's transfers and function
+    /// calls.
+    pub fn deposit(&self) -> Balance {
+        self.actions
+            .iter()
+            .map(|action| match action {
+                Action::Transfer(transfer) => transfer.deposit,
+                Action::FunctionCall(call) => call.deposit,
+                _ => 0,
+            })
+            .sum()
+    }
+
+    pub fn is_success(&self) -> bool {
+        matches!(
+            self.status,
+            ExecutionStatus::SuccessValue(_) | ExecutionStatus::SuccessReceiptId(_)
+        )
+    }
+
+    pub fn is_failure(&self) -> bool {
+        matches!(self.status, ExecutionStatus::Failure(_))
+    }
+
+    /// Iterates over this node and all of it's descendants, depth-first,
+    /// with each node's depth (starting at `0` for this node).
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &ReceiptNode)> {
+        let mut stack = vec![(0, self)];
+        std::iter::from_fn(move || {
+            let (depth, node) = stack.pop()?;
+            stack.extend(node.children.iter().rev().map(|child| (depth + 1, child)));
+            Some((depth, node))
+        })
+    }
+
+    /// Finds the first node (depth-first) that matches the `predicate`.
+    pub fn find(
+        &self,
+        predicate: impl Fn(&ReceiptNode) -> bool,
+    ) -> Option<&ReceiptNode> {
+        self.iter()
+            .map(|(_depth, node)| node)
+            .find(|node| predicate(node))
+    }
+
+    /// Finds all nodes (depth-first) that match the `predicate`.
+    pub fn find_all(
+        &self,
+        predicate: impl Fn(&ReceiptNode) -> bool,
+    ) -> Vec<&ReceiptNode> {
+        self.iter()
+            .map(|(_depth, node)| node)
+            .filter(|node| predicate(node))
+            .collect()
+    }
+
+    /// Finds the first node executed by `executor_id`.
+    pub fn find_by_executor(
+        &self,
+        executor_id: &str,
+    ) -> Option<&ReceiptNode> {
+        self.find(|node| node.executor_id.as_str() == executor_id)
+    }
+
+    /// Finds the first receipt executed by `executor_id` that calls
+    /// `method`.
+    pub fn find_call(
+        &self,
+        executor_id: &str,
+        method: &str,
+    ) -> Option<&ReceiptNode> {
+        self.find(|node| {
+            node.executor_id.as_str() == executor_id && node.method_names().contains(&method)
+        })
+    }
+
+    /// Finds the first node that has a log containing `text`.
+    pub fn find_by_log(
+        &self,
+        text: &str,
+    ) -> Option<&ReceiptNode> {
+        self.find(|node| node.logs.iter().any(|log| log.contains(text)))
+    }
+
+    /// Nodes that failed, together with their errors.
+    pub fn failures(&self) -> Vec<(&ReceiptNode, &TxExecutionError)> {
+        self.iter()
+            .filter_map(|(_depth, node)| match &node.status {
+                ExecutionStatus::Failure(err) => Some((node, err)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Finds the first node that failed with the `expected` kind of error.
+    pub fn find_failure(
+        &self,
+        expected: &FailureKind,
+    ) -> Option<&ReceiptNode> {
+        self.failures()
+            .into_iter()
+            .find(|(_node, err)| expected.matches(err))
+            .map(|(node, _err)| node)
+    }
+
+    /// Gas burnt by this node and all of it's descendants.
+    pub fn total_gas_burnt(&self) -> Gas {
+        Gas(self.iter().map(|(_depth, node)| node.gas_burnt.0).sum())
+    }
+
+    /// Tokens burnt by this node and all of it's descendants.
+    pub fn total_tokens_burnt(&self) -> Balance {
+        self.iter().map(|(_depth, node)| node.tokens_burnt).sum()
+    }
+
+    /// Logs of this node and all of it's descendants, depth-first.
+    pub fn all_logs(&self) -> Vec<String> {
+        self.iter()
+            .flat_map(|(_depth, node)| node.logs.clone())
+            .collect()
+    }
+
+    /// Renders the tree with each receipt indented under the one that
+    /// created it.
+    pub fn render(&self) -> String {
+        use std::fmt::Write;
+
+        let mut f = String::new();
+        for (depth, node) in self.iter() {
+            let indent = "  ".repeat(depth);
+            let methods = node.method_names();
+            let calls = if methods.is_empty() {
+                String::new()
+            } else {
+                format!(" - calls: {}", methods.join(", "))
+            };
+            writeln!(
+                f,
+                "{}{}{} - status: {} - gas burnt: {} - tokens burnt: {}",
+                indent,
+                node.executor_id,
+                calls,
+                pretty_status(node.status.clone()),
+                pretty_gas(node.gas_burnt),
+                pretty_near(node.tokens_burnt)
+            )
+            .unwrap();
+            for l in &node.logs {
+                writeln!(f, "{}- log: {}", indent, l).unwrap();
+            }
+        }
+        f
+    }
+
+    /// Represents the tree as JSON.
+    ///
+    /// The status is `{"success": <utf8 value>}`, `{"receipt": <id>}`,
+    /// `{"failure": <error>}` or `"unknown"`, and the tokens burnt are a
+    /// string.
+    pub fn to_json(&self) -> serde_json::Value {
+        let status = match &self.status {
+            ExecutionStatus::Unknown => json!("unknown"),
+            ExecutionStatus::Failure(e) => json!({ "failure": e.to_string() }),
+            ExecutionStatus::SuccessValue(v) => {
+                json!({ "success": pretty_utf8(v) })
+            }
+            ExecutionStatus::SuccessReceiptId(id) => json!({ "receipt": id.to_string() }),
+        };
+        json!({
+            "executor_id": self.executor_id,
+            "predecessor_id": self.predecessor_id,
+            "method_names": self.method_names(),
+            "status": status,
+            "gas_burnt": self.gas_burnt.0,
+            "tokens_burnt": self.tokens_burnt.to_string(),
+            "logs": self.logs,
+            "children": self
+                .children
+                .iter()
+                .map(Self::to_json)
+                .collect::<Vec<_>>(),
+        })
+    }
+}
+
+impl std::fmt::Display for ReceiptNode {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        f.write_str(&self.render())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gas::TGAS;
+    use crate::sim::contract_ext::{deploy_test_contract, ContractExt};
+    use crate::sim::debug::{self, DebugConfig};
+    use crate::sim::{runtime, BatchExt, ExecutionExt};
+    use near_sdk_sim::{init_simulator, to_yocto};
+
+    #[test]
+    fn transfer() {
+        let root = init_simulator(None);
+        let alice = root.create_user("alice".parse().unwrap(), to_yocto("10"));
+        let bob = root.create_user("bob".parse().unwrap(), to_yocto("10"));
+
+        let res = alice
+            .batch(bob.account_id())
+            .transfer(to_yocto("1"))
+            .submit::<()>();
+        let tree = res.receipt_tree();
+
+        // the transaction, and then it's receipt
+        assert_eq!(tree.executor_id, alice.account_id());
+        assert!(tree.is_success());
+        assert!(tree.actions.is_empty());
+        assert_eq!(tree.children[0].executor_id, bob.account_id());
+        assert!(matches!(
+            tree.children[0].actions[..],
+            [Action::Transfer(_)]
+        ));
+        assert_eq!(
+            tree.find_by_executor("bob").map(|node| node.gas_burnt),
+            Some(tree.children[0].gas_burnt)
+        );
+        assert!(tree.find_by_executor("carol").is_none());
+        assert!(tree.find(ReceiptNode::is_failure).is_none());
+
+        // same receipts as the flattened results
+        let flattened = res.promise_results().into_iter().flatten().count();
+        assert_eq!(tree.iter().count(), flattened);
+        assert_eq!(tree.total_gas_burnt(), res.total_gas_burnt());
+
+        let rendered = tree.render();
+        assert!(rendered.starts_with("alice - status: "));
+        assert!(rendered.contains("\n  bob - status: "));
+
+        let json = tree.to_json();
+        assert_eq!(json["executor_id"], "alice");
+        assert_eq!(json["children"][0]["executor_id"], "bob");
+
+        res.assert_no_failures();
+    }
+
+    #[test]
+    fn failure() {
+        let root = init_simulator(None);
+        let alice = root.create_user("alice".parse().unwrap(), to_yocto("10"));
+
+        // the transaction succeeds, but it's receipt fails
+        let res = alice
+            .batch("nobody".parse().unwrap())
+            .transfer(to_yocto("1"))
+            .submit::<()>();
+        let tree = res.receipt_tree();
+        assert!(tree.is_success());
+        assert_eq!(tree.failures().len(), 1);
+
+        let expected = FailureKind::AccountDoesNotExist("nobody".parse().unwrap());
+        assert_eq!(
+            tree.find_failure(&expected)
+                .map(|node| node.executor_id.as_str()),
+            Some("nobody")
+        );
+        res.assert_failure_kind(expected);
+    }
+
+    #[test]
+    #[should_panic(expected = "expected a failure matching MethodNotFound")]
+    fn failure_mismatch() {
+        let root = init_simulator(None);
+        let alice = root.create_user("alice".parse().unwrap(), to_yocto("10"));
+
+        let res = alice.transfer("nobody".parse().unwrap(), to_yocto("1"));
+        res.assert_failure_kind(FailureKind::MethodNotFound);
+    }
+
+    #[test]
+    fn find_call() {
+        let root = runtime::init_simulator(None);
+        let alice = root.create_user("alice".parse().unwrap(), to_yocto("10"));
+        let contract = deploy_test_contract(&root, "contract");
+        deploy_test_contract(&root, "other");
+
+        let gas = near_sdk::Gas(100 * TGAS);
+        let res = contract.json_call::<()>(&alice, "forward", json!("other"), gas, 0);
+        res.assert_no_failures();
+        let tree = res.receipt_tree();
+
+        let forward = tree.find_call("contract", "forward").unwrap();
+        assert_eq!(forward.method_names(), ["forward"]);
+        // created by the contract
+        let write = tree.find_call("other", "write").unwrap();
+        assert!(forward.children.contains(write));
+        assert!(tree.find_call("other", "forward").is_none());
+        assert!(tree.find_call("contract", "write").is_none());
+        assert!(tree
+            .render()
+            .contains("\n    other - calls: write - status: "));
+        assert_eq!(
+            tree.to_json()["children"][0]["method_names"],
+            json!(["forward"])
+        );
+    }
+
+    #[test]
+    fn find_call_without_receipts() {
+        // the simulator's own runtime drops the receipts that contracts create
+        let root = init_simulator(None);
+        let alice = root.create_user("alice".parse().unwrap(), to_yocto("10"));
+        let contract = deploy_test_contract(&root, "contract");
+        deploy_test_contract(&root, "other");
+
+        let gas = near_sdk::Gas(100 * TGAS);
+        let config = DebugConfig::buffer();
+        let res = debug::with_config(config.clone(), || {
+            contract.json_call::<()>(&alice, "forward", json!("other"), gas, 0)
+        });
+        let tree = res.receipt_tree();
+        assert!(tree.find_call("contract", "forward").is_some());
+        assert!(tree.find_by_executor("other").is_some());
+        assert!(tree.find_call("other", "write").is_none());
+        // which is reported
+        assert!(config
+            .sink
+            .take()
+            .contains("--- the runtime of alice was not created by sim::runtime::init_simulator"));
+    }
+}
@@ -0,0 +1,222 @@
+//! Submits transactions while keeping the receipts that they create.
+//!
+//! The simulator drops the receipts once they are executed, so only their
+//! outcomes could be inspected. The runtimes created by [`init_simulator`]
+//! are instead stepped one block at a time, which keeps the predecessor and
+//! actions of every receipt. For other runtimes, such as the ones created
+//! by [`near_sdk_sim::init_simulator`], only the receipt of the transaction
+//! itself is known, which is written to the debug output at
+//! [`Verbosity::Receipts`].
+//!
+//! Besides that, invalid transactions fail instead of panicking, so they can
+//! be asserted with [`FailureKind::NotEnoughBalance`](super::FailureKind).
+
+use super::contract_ext::Execution;
+use super::debug::{debugln, Verbosity};
+use near_sdk::serde::de::DeserializeOwned;
+use near_sdk::AccountId;
+use near_sdk_sim::errors::{RuntimeError, TxExecutionError};
+use near_sdk_sim::hash::CryptoHash;
+use near_sdk_sim::receipt::ReceiptEnum;
+use near_sdk_sim::runtime::{init_runtime, GenesisConfig, RuntimeStandalone};
+use near_sdk_sim::transaction::{
+    Action, ExecutionOutcome, ExecutionStatus, SignedTransaction, Transaction,
+};
+use near_sdk_sim::{outcome_into_result, UserAccount, UserTransaction};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+/// What is known of a receipt besides it's outcome.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReceiptInfo {
+    /// The account that created the receipt, which is `system` for refunds.
+    pub predecessor_id: AccountId,
+    pub actions: Vec<Action>,
+}
+
+/// Receipts, by receipt id.
+pub type Receipts = HashMap<CryptoHash, ReceiptInfo>;
+
+thread_local! {
+    /// Runtimes created by [`init_simulator`] on the current thread.
+    static RUNTIMES: RefCell<Vec<Weak<RefCell<RuntimeStandalone>>>> = RefCell::new(vec![]);
+}
+
+/// Creates the simulator, like [`near_sdk_sim::init_simulator`], but the
+/// transactions submitted through [`ContractExt`](super::ContractExt) and
+/// [`Batch`](super::Batch) keep all of their receipts.
+pub fn init_simulator(genesis_config: Option<GenesisConfig>) -> UserAccount {
+    let (runtime, signer, root_account_id) = init_runtime(genesis_config);
+    let runtime = Rc::new(RefCell::new(runtime));
+    RUNTIMES.with(|runtimes| {
+        let mut runtimes = runtimes.borrow_mut();
+        runtimes.retain(|runtime| runtime.strong_count() > 0);
+        runtimes.push(Rc::downgrade(&runtime));
+    });
+    UserAccount::new(&runtime, root_account_id, signer)
+}
+
+/// The runtime of the `account`, if it was created by [`init_simulator`].
+fn runtime_of(account: &UserAccount) -> Option<Rc<RefCell<RuntimeStandalone>>> {
+    let ptr: *const RuntimeStandalone = &*account.borrow_runtime();
+    RUNTIMES.with(|runtimes| {
+        runtimes
+            .borrow()
+            .iter()
+            .filter_map(Weak::upgrade)
+            .find(|runtime| std::ptr::eq(runtime.as_ptr(), ptr))
+    })
+}
+
+/// A transaction from the `signer` to the `receiver_id`, without actions.
+pub(crate) fn transaction(
+    signer: &UserAccount,
+    receiver_id: &AccountId,
+) -> Transaction {
+    let nonce = signer
+        .borrow_runtime()
+        .view_access_key(signer.account_id.as_str(), &signer.signer.public_key)
+        .unwrap_or_else(|| panic!("the signer {} has no access key", signer.account_id))
+        .nonce
+        + 1;
+    Transaction::new(
+        signer.account_id.to_string(),
+        signer.signer.public_key.clone(),
+        receiver_id.to_string(),
+        nonce,
+        CryptoHash::default(),
+    )
+}
+
+/// Signs and submits the `transaction`, like [`UserTransaction::submit`].
+///
+/// The execution also has the transaction itself, and it's receipts.
+pub(crate) fn submit<T>(
+    signer: &UserAccount,
+    transaction: Transaction,
+) -> Execution<T>
+where
+    T: DeserializeOwned,
+{
+    let tx_receipt = ReceiptInfo {
+        predecessor_id: signer.account_id(),
+        actions: transaction.actions.clone(),
+    };
+    let mut receipts = Receipts::new();
+    let result = match runtime_of(signer) {
+        Some(runtime) => {
+            let signed = transaction.sign(&signer.signer);
+            let outcome = step(&mut runtime.borrow_mut(), signed, &mut receipts);
+            outcome_into_result(outcome, &runtime)
+        }
+        None => {
+            debugln!(
+                Verbosity::Receipts,
+                "--- the runtime of {} was not created by sim::runtime::init_simulator, so only the receipt of the transaction is known ---",
+                signer.account_id
+            );
+            let receiver_id = transaction.receiver_id.parse().unwrap();
+            transaction
+                .actions
+                .into_iter()
+                .fold(signer.create_transaction(receiver_id), add_action)
+                .submit()
+        }
+    };
+
+    // the runtime lists the outcomes since the transaction was submitted,
+    // starting with the transaction's own
+    let tx = match signer.borrow_runtime().last_outcomes.first() {
+        Some(tx_hash) => result
+            .lookup_hash(tx_hash)
+            .expect("the runtime should have the outcome of the transaction"),
+        // an invalid transaction has no outcome in the runtime
+        None => result.clone(),
+    };
+    // the receipt of a transaction to it's own signer is executed right away,
+    // so it's not kept while stepping
+    if let Some(receipt_id) = tx.outcome().receipt_ids.first() {
+        receipts.insert(*receipt_id, tx_receipt);
+    }
+    Execution::new(result).with_transaction(tx, receipts)
+}
+
+/// Processes the `tx` and all of the receipts that it creates, block by
+/// block, keeping each receipt.
+///
+/// Returns the final outcome, like [`RuntimeStandalone::resolve_tx`]. If the
+/// `tx` is invalid, it's outcome is the failure, with nothing burnt.
+fn step(
+    runtime: &mut RuntimeStandalone,
+    tx: SignedTransaction,
+    receipts: &mut Receipts,
+) -> (CryptoHash, ExecutionOutcome) {
+    runtime.last_outcomes.clear();
+    let executor_id = tx.transaction.signer_id.clone();
+    let mut hash = runtime.send_tx(tx);
+    loop {
+        receipts.extend(runtime.pending_receipts().iter().filter_map(|receipt| {
+            match &receipt.receipt {
+                ReceiptEnum::Action(action) => Some((
+                    receipt.receipt_id,
+                    ReceiptInfo {
+                        predecessor_id: receipt.predecessor_id.parse().unwrap(),
+                        actions: action.actions.clone(),
+                    },
+                )),
+                ReceiptEnum::Data(_) => None,
+            }
+        }));
+        match runtime.produce_block() {
+            Ok(()) => {}
+            // the block only had the transaction, which was dropped
+            Err(RuntimeError::InvalidTxError(err)) => {
+                let outcome = ExecutionOutcome {
+                    executor_id,
+                    status: ExecutionStatus::Failure(TxExecutionError::InvalidTxError(err)),
+                    ..Default::default()
+                };
+                return (hash, outcome);
+            }
+            Err(err) => panic!(
+                "the runtime failed to produce a block for the transaction {}: {:?}",
+                hash, err
+            ),
+        }
+        if runtime.pending_receipts().is_empty() {
+            break;
+        }
+    }
+
+    loop {
+        let outcome = runtime.outcome(&hash).unwrap_or_else(|| {
+            panic!(
+                "the runtime has no outcome for {}, after processing all receipts",
+                hash
+            )
+        });
+        match outcome.status {
+            ExecutionStatus::SuccessReceiptId(receipt_id) => hash = receipt_id,
+            _ => return (hash, outcome),
+        }
+    }
+}
+
+fn add_action(
+    tx: UserTransaction,
+    action: Action,
+) -> UserTransaction {
+    match action {
+        Action::CreateAccount(_) => tx.create_account(),
+        Action::DeployContract(action) => tx.deploy_contract(action.code),
+        Action::FunctionCall(action) => {
+            tx.function_call(action.method_name, action.args, action.gas, action.deposit)
+        }
+        Action::Transfer(action) => tx.transfer(action.deposit),
+        Action::Stake(action) => tx.stake(action.stake, action.public_key),
+        Action::AddKey(action) => tx.add_key(action.public_key, action.access_key),
+        Action::DeleteKey(action) => tx.delete_key(action.public_key),
+        Action::DeleteAccount(action) => tx.delete_account(action.beneficiary_id.parse().unwrap()),
+    }
+}
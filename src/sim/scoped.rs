@@ -0,0 +1,132 @@
+//! State shared by clones, which can be made active for the current thread
+//! while a closure runs, so the helpers called inside the closure (such as
+//! [`ContractExt::json_call`](super::ContractExt::json_call)) can reach it
+//! without it being passed around.
+//!
+//! Tests run on their own threads, so the active state is per-test.
+
+use std::any::{Any, TypeId};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+thread_local! {
+    /// The active state of each type, as a `Scoped<T>`.
+    static ACTIVE: RefCell<HashMap<TypeId, Box<dyn Any>>> = RefCell::new(HashMap::new());
+}
+
+/// A `T` shared by clones, which can be made the active one of it's type for
+/// the current thread.
+#[derive(Debug, Default)]
+pub struct Scoped<T> {
+    inner: Arc<Mutex<T>>,
+}
+
+impl<T> Clone for Scoped<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<T: 'static> Scoped<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(value)),
+        }
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, T> {
+        self.inner.lock().unwrap()
+    }
+
+    /// Makes this the active `T` of the current thread while `f` runs, and
+    /// then restores the previous one, even if `f` panics.
+    pub fn activate<R>(
+        &self,
+        f: impl FnOnce() -> R,
+    ) -> R {
+        /// Restores the previous active state on drop.
+        struct Restore<T: 'static>(Option<Scoped<T>>);
+        impl<T: 'static> Drop for Restore<T> {
+            fn drop(&mut self) {
+                Scoped::replace_active(self.0.take());
+            }
+        }
+
+        let _restore = Restore(Self::replace_active(Some(self.clone())));
+        f()
+    }
+
+    /// Sets (or, with `None`, unsets) the active `T` of the current thread,
+    /// returning the previous one.
+    pub fn replace_active(scoped: Option<Self>) -> Option<Self> {
+        let key = TypeId::of::<T>();
+        let previous = ACTIVE.with(|active| {
+            let mut active = active.borrow_mut();
+            match scoped {
+                Some(scoped) => active.insert(key, Box::new(scoped)),
+                None => active.remove(&key),
+            }
+        });
+        previous.map(|previous| *previous.downcast::<Self>().unwrap())
+    }
+
+    /// The active `T` of the current thread, if any.
+    pub fn active() -> Option<Self> {
+        let key = TypeId::of::<T>();
+        ACTIVE.with(|active| {
+            active
+                .borrow()
+                .get(&key)
+                .map(|scoped| scoped.downcast_ref::<Self>().unwrap().clone())
+        })
+    }
+
+    pub fn is_active() -> bool {
+        Self::active().is_some()
+    }
+
+    /// Runs `f` on the active `T` of the current thread, if any.
+    ///
+    /// The thread's active state isn't borrowed while `f` runs, so `f` may
+    /// activate other states.
+    pub fn with_active<R>(f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        let scoped = Self::active()?;
+        let mut value = scoped.lock();
+        Some(f(&mut value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn activate() {
+        let outer = Scoped::new(Vec::<u8>::new());
+        let inner = Scoped::new(Vec::<u8>::new());
+        assert!(Scoped::<Vec<u8>>::with_active(|v| v.push(0)).is_none());
+
+        outer.activate(|| {
+            Scoped::<Vec<u8>>::with_active(|v| v.push(1));
+            inner.activate(|| Scoped::<Vec<u8>>::with_active(|v| v.push(2)));
+            // other types are independent
+            assert!(!Scoped::<String>::is_active());
+            Scoped::<Vec<u8>>::with_active(|v| v.push(3));
+        });
+        assert!(!Scoped::<Vec<u8>>::is_active());
+
+        assert_eq!(*outer.lock(), [1, 3]);
+        assert_eq!(*inner.lock(), [2]);
+    }
+
+    #[test]
+    fn restored_on_panic() {
+        let scoped = Scoped::new(0u32);
+        let res = std::panic::catch_unwind(|| scoped.activate(|| panic!("inside")));
+        assert!(res.is_err());
+        assert!(!Scoped::<u32>::is_active());
+    }
+}
@@ -0,0 +1,283 @@
+//! Tracks how the storage usage of contracts changes on each call made
+//! through [`ContractExt`](super::ContractExt) or [`Batch`](super::Batch),
+//! to check that the attached deposits cover it, such as when the contract
+//! uses [`refund_deposit`](crate::refund::refund_deposit).
+
+use super::execution_ext::pretty_near;
+use super::scoped::Scoped;
+use near_sdk::{AccountId, Balance};
+
+/// Storage usage of the called contract, before and after a call.
+///
+/// Only the storage of the called contract is tracked, and not of other
+/// contracts that it may call. An account that doesn't exist, such as one
+/// that was deleted, uses no storage.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageRecord {
+    pub contract_id: AccountId,
+    pub method: String,
+    pub signer_id: AccountId,
+    pub before: u64,
+    pub after: u64,
+    /// Deposit attached to the call.
+    pub deposit: Balance,
+    pub storage_price_per_byte: Balance,
+}
+
+impl StorageRecord {
+    /// Bytes added (or, if negative, released) by the call.
+    pub fn bytes_added(&self) -> i64 {
+        self.after as i64 - self.before as i64
+    }
+
+    /// Cost of the bytes added (or, if negative, released) by the call.
+    pub fn storage_cost(&self) -> i128 {
+        self.bytes_added() as i128 * self.storage_price_per_byte as i128
+    }
+
+    /// Whether the attached deposit covered the cost of the bytes added.
+    pub fn is_covered(&self) -> bool {
+        self.storage_cost() <= self.deposit as i128
+    }
+}
+
+impl std::fmt::Display for StorageRecord {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        let cost = self.storage_cost();
+        let sign = if cost < 0 { "-" } else { "" };
+        write!(
+            f,
+            "{}::{} by {}: {:+} bytes ({}{}), deposit {}",
+            self.contract_id,
+            self.method,
+            self.signer_id,
+            self.bytes_added(),
+            sign,
+            pretty_near(cost.unsigned_abs()),
+            pretty_near(self.deposit)
+        )
+    }
+}
+
+/// Collects the storage records of calls while it's active.
+///
+/// Clones share the same records.
+#[derive(Debug, Clone, Default)]
+pub struct StorageTracker {
+    records: Scoped<Vec<StorageRecord>>,
+}
+
+impl StorageTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Tracks the calls that the current thread makes while `f` runs.
+    pub fn track<R>(
+        &self,
+        f: impl FnOnce() -> R,
+    ) -> R {
+        self.records.activate(f)
+    }
+
+    pub fn records(&self) -> Vec<StorageRecord> {
+        self.records.lock().clone()
+    }
+
+    pub fn push(
+        &self,
+        record: StorageRecord,
+    ) {
+        self.records.lock().push(record);
+    }
+
+    /// One line per call, with the bytes added and the attached deposit.
+    pub fn report(&self) -> String {
+        self.records()
+            .iter()
+            .map(|record| format!("{}\n", record))
+            .collect()
+    }
+
+    /// Asserts that the deposit of every call covered the cost of the bytes
+    /// that it added.
+    pub fn assert_deposits_cover_storage(&self) {
+        let uncovered: Vec<String> = self
+            .records()
+            .iter()
+            .filter(|record| !record.is_covered())
+            .map(ToString::to_string)
+            .collect();
+        if !uncovered.is_empty() {
+            panic!(
+                "the deposits didn't cover the storage added:\n{}",
+                uncovered.join("\n")
+            );
+        }
+    }
+}
+
+/// Whether the current thread has an active tracker.
+pub(crate) fn is_active() -> bool {
+    Scoped::<Vec<StorageRecord>>::is_active()
+}
+
+/// Pushes the record into the current thread's active tracker, if any.
+pub(crate) fn record(record: impl FnOnce() -> StorageRecord) {
+    Scoped::<Vec<StorageRecord>>::with_active(|records| records.push(record()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::contract_ext::{deploy_test_contract, ContractExt, DummyContract};
+    use crate::sim::costs::RuntimeCosts;
+    use near_sdk::serde_json::json;
+    use near_sdk::utils::WithAccount;
+    use near_sdk_sim::{init_simulator, to_yocto, ContractAccount};
+
+    fn record(
+        before: u64,
+        after: u64,
+        deposit: Balance,
+    ) -> StorageRecord {
+        StorageRecord {
+            contract_id: "nft".parse().unwrap(),
+            method: "mint".into(),
+            signer_id: "alice".parse().unwrap(),
+            before,
+            after,
+            deposit,
+            storage_price_per_byte: near_sdk::env::STORAGE_PRICE_PER_BYTE,
+        }
+    }
+
+    #[test]
+    fn covered() {
+        let price = near_sdk::env::STORAGE_PRICE_PER_BYTE;
+        assert!(record(100, 100, 0).is_covered());
+        assert!(record(100, 110, 10 * price).is_covered());
+        assert!(!record(100, 110, 10 * price - 1).is_covered());
+        assert!(record(110, 100, 0).is_covered());
+        assert_eq!(record(110, 100, 0).storage_cost(), -10 * price as i128);
+        assert_eq!(
+            record(100, 1100, to_yocto("1")).to_string(),
+            "nft::mint by alice: +1000 bytes (10 mN), deposit 1 NEAR"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "nft::mint by alice: +10 bytes")]
+    fn uncovered() {
+        let tracker = StorageTracker::new();
+        tracker.push(record(100, 100, 0));
+        tracker.push(record(100, 110, 0));
+        tracker.assert_deposits_cover_storage();
+    }
+
+    #[test]
+    fn track() {
+        let root = init_simulator(None);
+        let alice = root.create_user("alice".parse().unwrap(), to_yocto("10"));
+        let bob_id: AccountId = "bob".parse().unwrap();
+        let bob = ContractAccount {
+            user_account: root.create_user(bob_id.clone(), to_yocto("10")),
+            contract: DummyContract::with_account(bob_id.clone()),
+        };
+
+        let tracker = StorageTracker::new();
+        let gas = near_sdk::Gas(near_sdk_sim::DEFAULT_GAS);
+        tracker.track(|| {
+            // bob has no contract, so it's storage doesn't change
+            let _ = bob.json_call::<()>(&alice, "method", json!({}), gas, 1);
+        });
+
+        let records = tracker.records();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].contract_id, bob_id);
+        assert_eq!(records[0].bytes_added(), 0);
+        assert_eq!(records[0].deposit, 1);
+        tracker.assert_deposits_cover_storage();
+    }
+
+    #[test]
+    fn track_writes() {
+        let root = init_simulator(None);
+        let alice = root.create_user("alice".parse().unwrap(), to_yocto("10"));
+        let contract = deploy_test_contract(&root, "contract");
+        let price = RuntimeCosts::new(&root).storage_price_per_byte;
+
+        // the key ("alice"), the value ("\"hello\"") and the record itself
+        let bytes = 5 + 7 + 40;
+        let tracker = StorageTracker::new();
+        let gas = near_sdk::Gas(near_sdk_sim::DEFAULT_GAS);
+        tracker.track(|| {
+            contract
+                .json_call::<()>(&alice, "write", json!("hello"), gas, bytes * price)
+                .assert_success();
+            contract
+                .json_call::<()>(&alice, "remove", json!({}), gas, 0)
+                .assert_success();
+        });
+
+        let records = tracker.records();
+        assert_eq!(records[0].bytes_added(), bytes as i64);
+        assert!(records[0].is_covered());
+        assert_eq!(records[1].bytes_added(), -(bytes as i64));
+        tracker.assert_deposits_cover_storage();
+    }
+
+    #[test]
+    fn only_the_called_contract() {
+        let root = init_simulator(None);
+        let alice = root.create_user("alice".parse().unwrap(), to_yocto("10"));
+        let contract = deploy_test_contract(&root, "contract");
+        let other = deploy_test_contract(&root, "other");
+        let other_before = other.account().unwrap().storage_usage;
+
+        let tracker = StorageTracker::new();
+        let gas = near_sdk::Gas(near_sdk_sim::DEFAULT_GAS);
+        tracker.track(|| {
+            contract
+                .json_call::<()>(&alice, "forward", json!("other"), gas, 0)
+                .assert_success();
+        });
+
+        // `other` stored what was forwarded to it, but only `contract` was
+        // tracked
+        assert!(other.account().unwrap().storage_usage > other_before);
+        let records = tracker.records();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].contract_id.as_str(), "contract");
+        assert_eq!(records[0].bytes_added(), 0);
+    }
+
+    #[test]
+    fn deleted_account() {
+        let root = init_simulator(None);
+        let alice = root.create_user("alice".parse().unwrap(), to_yocto("10"));
+        let bob_id: AccountId = "bob".parse().unwrap();
+        let bob = ContractAccount {
+            user_account: root.create_user(bob_id.clone(), to_yocto("10")),
+            contract: DummyContract::with_account(bob_id.clone()),
+        };
+        bob.user_account
+            .create_transaction(bob_id)
+            .delete_account(root.account_id())
+            .submit()
+            .assert_success();
+        assert!(bob.account().is_none());
+
+        let tracker = StorageTracker::new();
+        let gas = near_sdk::Gas(near_sdk_sim::DEFAULT_GAS);
+        tracker.track(|| {
+            let _ = bob.json_call::<()>(&alice, "method", json!({}), gas, 0);
+        });
+
+        let records = tracker.records();
+        assert_eq!((records[0].before, records[0].after), (0, 0));
+    }
+}
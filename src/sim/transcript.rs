@@ -0,0 +1,373 @@
+//! Records the calls, views and deploys made through
+//! [`ContractExt`](super::ContractExt), the accounts and transfers made
+//! through [`create_user`] and [`transfer`], and the actions of each
+//! [`Batch`](super::Batch), so a scenario can be replayed by hand with the
+//! `near` CLI.
+//!
+//! The recorded account ids are the ones from the simulation, which may need
+//! to be replaced before replaying on another network.
+
+use super::receipt_tree::ReceiptNode;
+use super::scoped::Scoped;
+use crate::js_integer::{JsU128, JsU64};
+use near_sdk::serde::Serialize;
+use near_sdk::serde_json;
+use near_sdk::{AccountId, Balance};
+use near_sdk_sim::{ExecutionResult, UserAccount};
+
+/// A recorded call, view, deploy, account creation or transfer.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TranscriptEntry {
+    Call {
+        contract_id: AccountId,
+        method: String,
+        args: serde_json::Value,
+        signer_id: AccountId,
+        gas: JsU64,
+        deposit: JsU128,
+        /// Whether the call failed in the simulation.
+        failed: bool,
+    },
+    View {
+        contract_id: AccountId,
+        method: String,
+        args: serde_json::Value,
+    },
+    Deploy {
+        contract_id: AccountId,
+        method: String,
+        args: serde_json::Value,
+        signer_id: AccountId,
+        gas: JsU64,
+        deposit: JsU128,
+    },
+    CreateAccount {
+        account_id: AccountId,
+        signer_id: AccountId,
+        balance: JsU128,
+    },
+    Send {
+        sender_id: AccountId,
+        receiver_id: AccountId,
+        amount: JsU128,
+        /// Whether the transfer failed in the simulation.
+        failed: bool,
+    },
+}
+
+impl TranscriptEntry {
+    /// The `near` CLI command that replays this entry.
+    ///
+    /// Deploys use `WASM_PATH` in place of the wasm file, and calls and
+    /// transfers that failed in the simulation are allowed to fail.
+    pub fn to_command(&self) -> String {
+        match self {
+            Self::Call {
+                contract_id,
+                method,
+                args,
+                signer_id,
+                gas,
+                deposit,
+                failed,
+            } => format!(
+                "near call {} {} {} --accountId {} --gas {} --depositYocto {}{}",
+                contract_id,
+                method,
+                shell_quote(&args.to_string()),
+                signer_id,
+                gas.get(),
+                deposit.get(),
+                allow_failure(*failed)
+            ),
+            Self::View {
+                contract_id,
+                method,
+                args,
+            } => format!(
+                "near view {} {} {}",
+                contract_id,
+                method,
+                shell_quote(&args.to_string())
+            ),
+            Self::Deploy {
+                contract_id,
+                method,
+                args,
+                signer_id: _,
+                gas,
+                deposit,
+            } => format!(
+                "near deploy --wasmFile \"$WASM_PATH\" --contractName {} --initFunction {} --initArgs {} --initGas {} --initDeposit {}",
+                contract_id,
+                method,
+                shell_quote(&args.to_string()),
+                gas.get(),
+                crate::yocto_to_near(deposit.get())
+            ),
+            Self::CreateAccount {
+                account_id,
+                signer_id,
+                balance,
+            } => format!(
+                "near create-account {} --masterAccount {} --initialBalance {}",
+                account_id,
+                signer_id,
+                crate::yocto_to_near(balance.get())
+            ),
+            Self::Send {
+                sender_id,
+                receiver_id,
+                amount,
+                failed,
+            } => format!(
+                "near send {} {} {}{}",
+                sender_id,
+                receiver_id,
+                crate::yocto_to_near(amount.get()),
+                allow_failure(*failed)
+            ),
+        }
+    }
+}
+
+/// Lets a command that failed in the simulation fail.
+fn allow_failure(failed: bool) -> &'static str {
+    if failed {
+        " || true # failed in the simulation"
+    } else {
+        ""
+    }
+}
+
+/// Quotes `s` as a single shell word.
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+/// Collects the entries recorded while it's active.
+///
+/// Clones share the same entries.
+#[derive(Debug, Clone, Default)]
+pub struct Recorder {
+    entries: Scoped<Vec<TranscriptEntry>>,
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records what the current thread calls, views and deploys while `f`
+    /// runs.
+    pub fn record<R>(
+        &self,
+        f: impl FnOnce() -> R,
+    ) -> R {
+        self.entries.activate(f)
+    }
+
+    pub fn entries(&self) -> Vec<TranscriptEntry> {
+        self.entries.lock().clone()
+    }
+
+    pub fn push(
+        &self,
+        entry: TranscriptEntry,
+    ) {
+        self.entries.lock().push(entry);
+    }
+
+    /// The entries as a JSON array.
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::to_value(self.entries()).unwrap()
+    }
+
+    /// The entries as a shell script of `near` CLI commands.
+    pub fn to_script(&self) -> String {
+        let mut script = String::from(
+            "#!/usr/bin/env bash\n\
+             # Replays a simulated scenario.\n\
+             # Deploys expect WASM_PATH to be set to the contract's wasm file.\n\
+             set -e\n\n",
+        );
+        for entry in self.entries() {
+            script.push_str(&entry.to_command());
+            script.push('\n');
+        }
+        script
+    }
+}
+
+/// Pushes the entry into the current thread's active recorder, if any.
+///
+/// The entry is only created if there is an active recorder.
+pub(crate) fn record(entry: impl FnOnce() -> TranscriptEntry) {
+    Scoped::<Vec<TranscriptEntry>>::with_active(|entries| entries.push(entry()));
+}
+
+/// Creates a user, like [`UserAccount::create_user`], recording it as
+/// `near create-account`.
+pub fn create_user(
+    signer: &UserAccount,
+    account_id: AccountId,
+    balance: Balance,
+) -> UserAccount {
+    let user = signer.create_user(account_id.clone(), balance);
+    record(|| TranscriptEntry::CreateAccount {
+        account_id,
+        signer_id: signer.account_id(),
+        balance: balance.into(),
+    });
+    user
+}
+
+/// Transfers `amount` to the `receiver_id`, like [`UserAccount::transfer`],
+/// recording it as `near send`.
+pub fn transfer(
+    sender: &UserAccount,
+    receiver_id: AccountId,
+    amount: Balance,
+) -> ExecutionResult {
+    let res = sender.transfer(receiver_id.clone(), amount);
+    record(|| TranscriptEntry::Send {
+        sender_id: sender.account_id(),
+        receiver_id,
+        amount: amount.into(),
+        failed: !ReceiptNode::new(&res).failures().is_empty(),
+    });
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::contract_ext::{ContractExt, DummyContract};
+    use near_sdk::serde_json::json;
+    use near_sdk::utils::WithAccount;
+    use near_sdk_sim::{init_simulator, to_yocto, ContractAccount};
+
+    #[test]
+    fn script() {
+        let recorder = Recorder::new();
+        recorder.push(TranscriptEntry::Deploy {
+            contract_id: "nft".parse().unwrap(),
+            method: "new".into(),
+            args: json!({"owner_id": "alice"}),
+            signer_id: "root".parse().unwrap(),
+            gas: JsU64::new(300_000_000_000_000),
+            deposit: JsU128::new(to_yocto("1")),
+        });
+        recorder.push(TranscriptEntry::Call {
+            contract_id: "nft".parse().unwrap(),
+            method: "set_name".into(),
+            args: json!({"name": "it's"}),
+            signer_id: "alice".parse().unwrap(),
+            gas: JsU64::new(10),
+            deposit: JsU128::new(1),
+            failed: true,
+        });
+        recorder.push(TranscriptEntry::View {
+            contract_id: "nft".parse().unwrap(),
+            method: "name".into(),
+            args: json!({}),
+        });
+        recorder.push(TranscriptEntry::CreateAccount {
+            account_id: "bob".parse().unwrap(),
+            signer_id: "root".parse().unwrap(),
+            balance: JsU128::new(to_yocto("10")),
+        });
+        recorder.push(TranscriptEntry::Send {
+            sender_id: "alice".parse().unwrap(),
+            receiver_id: "bob".parse().unwrap(),
+            amount: JsU128::new(to_yocto("0.5")),
+            failed: false,
+        });
+
+        let script = recorder.to_script();
+        let commands: Vec<&str> = script.lines().skip(5).collect();
+        assert_eq!(
+            commands,
+            [
+                r#"near deploy --wasmFile "$WASM_PATH" --contractName nft --initFunction new --initArgs '{"owner_id":"alice"}' --initGas 300000000000000 --initDeposit 1.000000000000000000000000"#,
+                r#"near call nft set_name '{"name":"it'\''s"}' --accountId alice --gas 10 --depositYocto 1 || true # failed in the simulation"#,
+                r#"near view nft name '{}'"#,
+                "near create-account bob --masterAccount root --initialBalance 10.000000000000000000000000",
+                "near send alice bob 0.500000000000000000000000",
+            ]
+        );
+
+        let json = recorder.to_json();
+        assert_eq!(json[0]["kind"], "deploy");
+        assert_eq!(json[0]["deposit"], to_yocto("1").to_string());
+        assert_eq!(json[1]["kind"], "call");
+        assert_eq!(json[1]["failed"], true);
+        assert_eq!(json[2]["kind"], "view");
+        assert_eq!(json[3]["kind"], "create_account");
+        assert_eq!(json[4]["kind"], "send");
+    }
+
+    #[test]
+    fn record() {
+        let root = init_simulator(None);
+        let alice = root.create_user("alice".parse().unwrap(), to_yocto("10"));
+        let bob_id: AccountId = "bob".parse().unwrap();
+        let bob = ContractAccount {
+            user_account: root.create_user(bob_id.clone(), to_yocto("10")),
+            contract: DummyContract::with_account(bob_id.clone()),
+        };
+
+        let recorder = Recorder::new();
+        let gas = near_sdk::Gas(near_sdk_sim::DEFAULT_GAS);
+        let carol = recorder.record(|| {
+            // bob has no contract, so these fail
+            let _ = bob.json_call::<()>(&alice, "method", json!({"a": 1}), gas, 1);
+            let _ = bob.json_view::<()>("view", json!({}));
+            let carol = create_user(&root, "carol".parse().unwrap(), to_yocto("5"));
+            transfer(&carol, bob_id.clone(), to_yocto("1")).assert_success();
+            let _ = transfer(&carol, "nobody".parse().unwrap(), to_yocto("1"));
+            carol
+        });
+        // not recorded
+        let _ = bob.json_view::<()>("other", json!({}));
+
+        assert_eq!(
+            recorder.entries(),
+            [
+                TranscriptEntry::Call {
+                    contract_id: bob_id.clone(),
+                    method: "method".into(),
+                    args: json!({"a": 1}),
+                    signer_id: alice.account_id(),
+                    gas: JsU64::new(gas.0),
+                    deposit: JsU128::new(1),
+                    failed: true,
+                },
+                TranscriptEntry::View {
+                    contract_id: bob_id.clone(),
+                    method: "view".into(),
+                    args: json!({}),
+                },
+                TranscriptEntry::CreateAccount {
+                    account_id: carol.account_id(),
+                    signer_id: root.account_id(),
+                    balance: JsU128::new(to_yocto("5")),
+                },
+                TranscriptEntry::Send {
+                    sender_id: carol.account_id(),
+                    receiver_id: bob_id,
+                    amount: JsU128::new(to_yocto("1")),
+                    failed: false,
+                },
+                TranscriptEntry::Send {
+                    sender_id: carol.account_id(),
+                    receiver_id: "nobody".parse().unwrap(),
+                    amount: JsU128::new(to_yocto("1")),
+                    failed: true,
+                },
+            ]
+        );
+    }
+}
@@ -1,14 +1,20 @@
 use near_sdk::{
     borsh::{self, BorshDeserialize, BorshSerialize},
     schemars::JsonSchema,
-    serde::{Deserialize, Serialize},
+    serde::{de::DeserializeOwned, Deserialize, Serialize},
     serde_json,
 };
 
+mod patch;
+mod schema;
+
+pub use patch::{PatchEvent, PointerError};
+pub use schema::{CompiledSchema, Violation};
+
 /// Wrapper that implements borsh de/serialization for [`serde_json::Value`].
 ///
 /// For borsh, the structure is considered a `Vec<u8>`.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 #[serde(crate = "near_sdk::serde")]
 #[serde(transparent)]
 #[schemars(crate = "near_sdk::schemars")]
@@ -49,3 +55,978 @@ impl From<Value> for serde_json::Value {
         value.inner
     }
 }
+
+impl std::ops::Deref for Value {
+    type Target = serde_json::Value;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl Value {
+    /// Serializes `value` into a [`Value`].
+    pub fn from_typed<T: Serialize>(value: &T) -> Result<Self, serde_json::Error> {
+        serde_json::to_value(value).map(Self::from)
+    }
+
+    /// Deserializes the value into a `T`.
+    pub fn to_typed<T: DeserializeOwned>(&self) -> Result<T, serde_json::Error> {
+        T::deserialize(&self.inner)
+    }
+
+    /// Size, in bytes, of the value serialized as JSON text.
+    pub fn json_size(&self) -> usize {
+        serde_json::to_vec(&self.inner)
+            .map(|bytes| bytes.len())
+            .unwrap_or(usize::MAX)
+    }
+
+    /// Nesting depth of arrays and objects.  
+    /// Eg. `1` has depth `0`, `[]` has depth `1` and `{"a": [1]}` has
+    /// depth `2`.
+    pub fn depth(&self) -> usize {
+        fn depth(v: &serde_json::Value) -> usize {
+            match v {
+                serde_json::Value::Array(a) => 1 + a.iter().map(depth).max().unwrap_or(0),
+                serde_json::Value::Object(o) => 1 + o.values().map(depth).max().unwrap_or(0),
+                _ => 0,
+            }
+        }
+        depth(&self.inner)
+    }
+
+    /// Checks the value against size and depth limits.
+    pub fn check_limits(
+        &self,
+        max_bytes: usize,
+        max_depth: usize,
+    ) -> Result<(), ValueLimitError> {
+        let depth = self.depth();
+        if depth > max_depth {
+            return Err(ValueLimitError::TooDeep {
+                depth,
+                max: max_depth,
+            });
+        }
+        let size = self.json_size();
+        if size > max_bytes {
+            return Err(ValueLimitError::TooLarge {
+                size,
+                max: max_bytes,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Why a [`Value`] doesn't fit in a [`BoundedValue`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValueLimitError {
+    TooLarge { size: usize, max: usize },
+    TooDeep { depth: usize, max: usize },
+}
+
+impl std::fmt::Display for ValueLimitError {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        match self {
+            Self::TooLarge { size, max } => write!(
+                f,
+                "JSON value has {} bytes but at most {} are allowed",
+                size, max
+            ),
+            Self::TooDeep { depth, max } => write!(
+                f,
+                "JSON value has depth {} but at most {} is allowed",
+                depth, max
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ValueLimitError {}
+
+/// A [`Value`] which, serialized as JSON text, has at most `MAX_BYTES`
+/// bytes and at most `MAX_DEPTH` nested arrays or objects.
+///
+/// The limits are checked when it's created and on both JSON and borsh
+/// deserialization. When deserializing, they are checked while the value is
+/// parsed, so an oversized or too deeply nested input fails before it's
+/// fully allocated. For borsh, the size is also checked before the value is
+/// parsed. Otherwise it de/serializes the same as [`Value`].
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(transparent)]
+pub struct BoundedValue<const MAX_BYTES: usize, const MAX_DEPTH: usize> {
+    inner: Value,
+}
+
+impl<const MAX_BYTES: usize, const MAX_DEPTH: usize> BoundedValue<MAX_BYTES, MAX_DEPTH> {
+    pub fn new(value: Value) -> Result<Self, ValueLimitError> {
+        value.check_limits(MAX_BYTES, MAX_DEPTH)?;
+        Ok(Self { inner: value })
+    }
+
+    pub fn as_value(&self) -> &Value {
+        &self.inner
+    }
+
+    pub fn into_value(self) -> Value {
+        self.inner
+    }
+}
+
+impl<const MAX_BYTES: usize, const MAX_DEPTH: usize> std::convert::TryFrom<Value>
+    for BoundedValue<MAX_BYTES, MAX_DEPTH>
+{
+    type Error = ValueLimitError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        Self::new(value)
+    }
+}
+
+impl<const MAX_BYTES: usize, const MAX_DEPTH: usize> From<BoundedValue<MAX_BYTES, MAX_DEPTH>>
+    for Value
+{
+    fn from(value: BoundedValue<MAX_BYTES, MAX_DEPTH>) -> Self {
+        value.inner
+    }
+}
+
+impl<const MAX_BYTES: usize, const MAX_DEPTH: usize> JsonSchema
+    for BoundedValue<MAX_BYTES, MAX_DEPTH>
+{
+    fn schema_name() -> String {
+        Value::schema_name()
+    }
+    fn json_schema(
+        gen: &mut near_sdk::schemars::gen::SchemaGenerator
+    ) -> near_sdk::schemars::schema::Schema {
+        Value::json_schema(gen)
+    }
+}
+
+impl<'de, const MAX_BYTES: usize, const MAX_DEPTH: usize> Deserialize<'de>
+    for BoundedValue<MAX_BYTES, MAX_DEPTH>
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: near_sdk::serde::Deserializer<'de>,
+    {
+        use near_sdk::serde::de::DeserializeSeed;
+
+        let mut size = 0;
+        let seed = LimitedSeed {
+            depth: 0,
+            max_depth: MAX_DEPTH,
+            max_bytes: MAX_BYTES,
+            size: &mut size,
+        };
+        let value: Value = seed.deserialize(deserializer)?.into();
+        // duplicated keys may have been counted more than once
+        Self::new(value).map_err(near_sdk::serde::de::Error::custom)
+    }
+}
+
+/// Deserializes a [`serde_json::Value`] while counting the size of it's
+/// JSON text (without whitespace) and it's depth, failing as soon as either
+/// goes over it's limit.
+struct LimitedSeed<'s> {
+    /// Depth of the value being deserialized.
+    depth: usize,
+    max_depth: usize,
+    max_bytes: usize,
+    /// Size of what was deserialized so far.
+    size: &'s mut usize,
+}
+
+impl<'s> LimitedSeed<'s> {
+    fn add<E: near_sdk::serde::de::Error>(
+        &mut self,
+        bytes: usize,
+    ) -> Result<(), E> {
+        *self.size += bytes;
+        if *self.size > self.max_bytes {
+            return Err(E::custom(ValueLimitError::TooLarge {
+                size: *self.size,
+                max: self.max_bytes,
+            }));
+        }
+        Ok(())
+    }
+
+    /// Seed for an item of the array or object being deserialized.
+    fn nested<E: near_sdk::serde::de::Error>(&mut self) -> Result<LimitedSeed<'_>, E> {
+        let depth = self.depth + 1;
+        if depth > self.max_depth {
+            return Err(E::custom(ValueLimitError::TooDeep {
+                depth,
+                max: self.max_depth,
+            }));
+        }
+        Ok(LimitedSeed {
+            depth,
+            max_depth: self.max_depth,
+            max_bytes: self.max_bytes,
+            size: &mut *self.size,
+        })
+    }
+}
+
+/// Size of the string as JSON text, as escaped by `serde_json`.
+fn json_str_size(s: &str) -> usize {
+    2 + s
+        .chars()
+        .map(|c| match c {
+            '"' | '\\' | '\n' | '\r' | '\t' | '\u{8}' | '\u{c}' => 2,
+            c if (c as u32) < 0x20 => 6,
+            c => c.len_utf8(),
+        })
+        .sum::<usize>()
+}
+
+impl<'de, 's> near_sdk::serde::de::DeserializeSeed<'de> for LimitedSeed<'s> {
+    type Value = serde_json::Value;
+
+    fn deserialize<D>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error>
+    where
+        D: near_sdk::serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de, 's> near_sdk::serde::de::Visitor<'de> for LimitedSeed<'s> {
+    type Value = serde_json::Value;
+
+    fn expecting(
+        &self,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        write!(
+            f,
+            "a JSON value with at most {} bytes and depth {}",
+            self.max_bytes, self.max_depth
+        )
+    }
+
+    fn visit_unit<E: near_sdk::serde::de::Error>(mut self) -> Result<Self::Value, E> {
+        self.add(4)?;
+        Ok(serde_json::Value::Null)
+    }
+
+    fn visit_none<E: near_sdk::serde::de::Error>(self) -> Result<Self::Value, E> {
+        self.visit_unit()
+    }
+
+    fn visit_some<D>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error>
+    where
+        D: near_sdk::serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_bool<E: near_sdk::serde::de::Error>(
+        mut self,
+        b: bool,
+    ) -> Result<Self::Value, E> {
+        self.add(if b { 4 } else { 5 })?;
+        Ok(serde_json::Value::Bool(b))
+    }
+
+    fn visit_i64<E: near_sdk::serde::de::Error>(
+        mut self,
+        n: i64,
+    ) -> Result<Self::Value, E> {
+        self.add(n.to_string().len())?;
+        Ok(n.into())
+    }
+
+    fn visit_u64<E: near_sdk::serde::de::Error>(
+        mut self,
+        n: u64,
+    ) -> Result<Self::Value, E> {
+        self.add(n.to_string().len())?;
+        Ok(n.into())
+    }
+
+    fn visit_f64<E: near_sdk::serde::de::Error>(
+        mut self,
+        n: f64,
+    ) -> Result<Self::Value, E> {
+        let value = serde_json::Number::from_f64(n).map_or(serde_json::Value::Null, Into::into);
+        self.add(value.to_string().len())?;
+        Ok(value)
+    }
+
+    fn visit_str<E: near_sdk::serde::de::Error>(
+        mut self,
+        s: &str,
+    ) -> Result<Self::Value, E> {
+        self.add(json_str_size(s))?;
+        Ok(serde_json::Value::String(s.to_string()))
+    }
+
+    fn visit_string<E: near_sdk::serde::de::Error>(
+        mut self,
+        s: String,
+    ) -> Result<Self::Value, E> {
+        self.add(json_str_size(&s))?;
+        Ok(serde_json::Value::String(s))
+    }
+
+    fn visit_seq<A>(
+        mut self,
+        mut seq: A,
+    ) -> Result<Self::Value, A::Error>
+    where
+        A: near_sdk::serde::de::SeqAccess<'de>,
+    {
+        // the brackets
+        self.add(2)?;
+        let mut items = vec![];
+        while let Some(item) = seq.next_element_seed(self.nested()?)? {
+            if !items.is_empty() {
+                // the comma
+                self.add(1)?;
+            }
+            items.push(item);
+        }
+        Ok(serde_json::Value::Array(items))
+    }
+
+    fn visit_map<A>(
+        mut self,
+        mut map: A,
+    ) -> Result<Self::Value, A::Error>
+    where
+        A: near_sdk::serde::de::MapAccess<'de>,
+    {
+        // the braces
+        self.add(2)?;
+        let mut members = serde_json::Map::new();
+        while let Some(key) = map.next_key::<String>()? {
+            // the key and colon, and the comma
+            let comma = if members.is_empty() { 0 } else { 1 };
+            self.add(json_str_size(&key) + 1 + comma)?;
+            let value = map.next_value_seed(self.nested()?)?;
+            members.insert(key, value);
+        }
+        Ok(serde_json::Value::Object(members))
+    }
+}
+
+impl<const MAX_BYTES: usize, const MAX_DEPTH: usize> BorshSerialize
+    for BoundedValue<MAX_BYTES, MAX_DEPTH>
+{
+    fn serialize<W: std::io::Write>(
+        &self,
+        writer: &mut W,
+    ) -> std::io::Result<()> {
+        borsh::BorshSerialize::serialize(&self.inner, writer)
+    }
+}
+
+impl<const MAX_BYTES: usize, const MAX_DEPTH: usize> BorshDeserialize
+    for BoundedValue<MAX_BYTES, MAX_DEPTH>
+{
+    fn deserialize(buf: &mut &[u8]) -> std::io::Result<Self> {
+        let invalid = |err| std::io::Error::new(std::io::ErrorKind::InvalidData, err);
+
+        // peek the length of the JSON text before reading it
+        let size: u32 = borsh::BorshDeserialize::deserialize(&mut &buf[..])?;
+        if size as usize > MAX_BYTES {
+            return Err(invalid(ValueLimitError::TooLarge {
+                size: size as usize,
+                max: MAX_BYTES,
+            }));
+        }
+
+        let bytes: Vec<u8> = borsh::BorshDeserialize::deserialize(buf)?;
+        serde_json::from_slice(&bytes)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+    }
+}
+
+/// A [`Value`] with a structural borsh encoding, which is smaller than the
+/// JSON text used by [`Value`] and doesn't need to be re-parsed as text.
+///
+/// For borsh, the structure starts with the `u32` [`CompactValue::MARKER`]
+/// followed by an `u8` version and then the value itself. Anything that
+/// doesn't start with the marker is read as a [`Value`] instead, so records
+/// stored as JSON text can still be read (and are re-written in the compact
+/// form). Otherwise it de/serializes the same as [`Value`].
+///
+/// Each value (version `1`) is a tag byte followed by it's content:
+/// - `0`, `1`, `2`: `null`, `false` and `true`;
+/// - `3`: a non-negative integer, as a varint;
+/// - `4`: a negative integer `n`, as the varint of `-(n + 1)`;
+/// - `5`: a float, as a little-endian `f64`;
+/// - `6`: a string, as the varint of it's length and it's utf8 bytes;
+/// - `7`: an array, as the varint of it's length and it's values;
+/// - `8`: an object, as the varint of it's length and each key (as a
+///   string without the tag) and value.
+///
+/// Varints are unsigned LEB128.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(transparent)]
+#[schemars(crate = "near_sdk::schemars")]
+pub struct CompactValue(pub Value);
+
+impl CompactValue {
+    /// Marks the compact encoding. A [`Value`] this large could not be
+    /// stored.
+    pub const MARKER: u32 = u32::MAX;
+    /// Current version of the compact encoding.
+    pub const VERSION: u8 = 1;
+    /// Maximum nesting depth accepted when decoding.
+    pub const MAX_DEPTH: usize = 128;
+}
+
+impl From<Value> for CompactValue {
+    fn from(v: Value) -> Self {
+        Self(v)
+    }
+}
+
+impl From<CompactValue> for Value {
+    fn from(v: CompactValue) -> Self {
+        v.0
+    }
+}
+
+impl BorshSerialize for CompactValue {
+    fn serialize<W: std::io::Write>(
+        &self,
+        writer: &mut W,
+    ) -> std::io::Result<()> {
+        borsh::BorshSerialize::serialize(&Self::MARKER, writer)?;
+        borsh::BorshSerialize::serialize(&Self::VERSION, writer)?;
+        compact::write_value(&self.0.inner, writer)
+    }
+}
+
+impl BorshDeserialize for CompactValue {
+    fn deserialize(buf: &mut &[u8]) -> std::io::Result<Self> {
+        // peek for the marker
+        let marker: Option<u32> = if buf.len() >= 4 {
+            Some(borsh::BorshDeserialize::deserialize(&mut &buf[..])?)
+        } else {
+            None
+        };
+        if marker != Some(Self::MARKER) {
+            let legacy: Value = borsh::BorshDeserialize::deserialize(buf)?;
+            return Ok(Self(legacy));
+        }
+
+        *buf = &buf[4..];
+        let version: u8 = borsh::BorshDeserialize::deserialize(buf)?;
+        if version != Self::VERSION {
+            return Err(compact::invalid(format!(
+                "unknown compact value version {}",
+                version
+            )));
+        }
+        let inner = compact::read_value(buf, Self::MAX_DEPTH)?;
+        Ok(Self(inner.into()))
+    }
+}
+
+/// A [`Value`] that is expected to hold a `T`.
+///
+/// It's stored exactly as a [`Value`], so a [`Value`] field can become a
+/// `TypedValue<T>` without migrating it's records, and the stored JSON
+/// is only converted into a `T` when it's read.
+///
+/// On JSON deserialization, the value must be a valid `T`. On borsh
+/// deserialization it's not checked, so records that were stored before
+/// the field became typed can still be read (and fixed).
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(transparent)]
+pub struct TypedValue<T> {
+    inner: Value,
+    #[serde(skip)]
+    _phantom: std::marker::PhantomData<fn() -> T>,
+}
+
+impl<T> TypedValue<T> {
+    /// Wraps a value without checking that it's a valid `T`.
+    pub fn from_value_unchecked(value: Value) -> Self {
+        Self {
+            inner: value,
+            _phantom: std::marker::PhantomData,
+        }
+    }
+
+    pub fn as_value(&self) -> &Value {
+        &self.inner
+    }
+
+    pub fn into_value(self) -> Value {
+        self.inner
+    }
+}
+
+impl<T: Serialize> TypedValue<T> {
+    pub fn new(value: &T) -> Result<Self, serde_json::Error> {
+        Value::from_typed(value).map(Self::from_value_unchecked)
+    }
+
+    /// Replaces the stored value.
+    pub fn set(
+        &mut self,
+        value: &T,
+    ) -> Result<(), serde_json::Error> {
+        self.inner = Value::from_typed(value)?;
+        Ok(())
+    }
+}
+
+impl<T: DeserializeOwned> TypedValue<T> {
+    /// Deserializes the stored value into a `T`.
+    pub fn get(&self) -> Result<T, serde_json::Error> {
+        self.inner.to_typed()
+    }
+}
+
+impl<T: DeserializeOwned> std::convert::TryFrom<Value> for TypedValue<T> {
+    type Error = serde_json::Error;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        value.to_typed::<T>()?;
+        Ok(Self::from_value_unchecked(value))
+    }
+}
+
+impl<T> From<TypedValue<T>> for Value {
+    fn from(value: TypedValue<T>) -> Self {
+        value.inner
+    }
+}
+
+impl<T> Clone for TypedValue<T> {
+    fn clone(&self) -> Self {
+        Self::from_value_unchecked(self.inner.clone())
+    }
+}
+
+impl<T> std::fmt::Debug for TypedValue<T> {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        f.debug_tuple("TypedValue").field(&self.inner).finish()
+    }
+}
+
+impl<T> PartialEq for TypedValue<T> {
+    fn eq(
+        &self,
+        other: &Self,
+    ) -> bool {
+        self.inner == other.inner
+    }
+}
+
+impl<T> Eq for TypedValue<T> {}
+
+impl<T: JsonSchema> JsonSchema for TypedValue<T> {
+    fn schema_name() -> String {
+        T::schema_name()
+    }
+    fn json_schema(
+        gen: &mut near_sdk::schemars::gen::SchemaGenerator
+    ) -> near_sdk::schemars::schema::Schema {
+        T::json_schema(gen)
+    }
+}
+
+impl<'de, T: DeserializeOwned> Deserialize<'de> for TypedValue<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: near_sdk::serde::Deserializer<'de>,
+    {
+        let value: Value = Deserialize::deserialize(deserializer)?;
+        Self::try_from(value).map_err(near_sdk::serde::de::Error::custom)
+    }
+}
+
+impl<T> BorshSerialize for TypedValue<T> {
+    fn serialize<W: std::io::Write>(
+        &self,
+        writer: &mut W,
+    ) -> std::io::Result<()> {
+        borsh::BorshSerialize::serialize(&self.inner, writer)
+    }
+}
+
+impl<T> BorshDeserialize for TypedValue<T> {
+    fn deserialize(buf: &mut &[u8]) -> std::io::Result<Self> {
+        let value: Value = borsh::BorshDeserialize::deserialize(buf)?;
+        Ok(Self::from_value_unchecked(value))
+    }
+}
+
+/// Structural encoding used by [`CompactValue`].
+mod compact {
+    use near_sdk::serde_json::{Map, Number, Value};
+    use std::io::{Error, ErrorKind, Result, Write};
+
+    const NULL: u8 = 0;
+    const FALSE: u8 = 1;
+    const TRUE: u8 = 2;
+    const UINT: u8 = 3;
+    const NEG_INT: u8 = 4;
+    const FLOAT: u8 = 5;
+    const STRING: u8 = 6;
+    const ARRAY: u8 = 7;
+    const OBJECT: u8 = 8;
+
+    pub fn invalid(msg: String) -> Error {
+        Error::new(ErrorKind::InvalidData, msg)
+    }
+
+    fn write_varint<W: Write>(
+        mut n: u64,
+        writer: &mut W,
+    ) -> Result<()> {
+        loop {
+            let byte = (n & 0x7f) as u8;
+            n >>= 7;
+            if n == 0 {
+                return writer.write_all(&[byte]);
+            }
+            writer.write_all(&[byte | 0x80])?;
+        }
+    }
+
+    fn write_str<W: Write>(
+        s: &str,
+        writer: &mut W,
+    ) -> Result<()> {
+        write_varint(s.len() as u64, writer)?;
+        writer.write_all(s.as_bytes())
+    }
+
+    pub fn write_value<W: Write>(
+        v: &Value,
+        writer: &mut W,
+    ) -> Result<()> {
+        match v {
+            Value::Null => writer.write_all(&[NULL]),
+            Value::Bool(false) => writer.write_all(&[FALSE]),
+            Value::Bool(true) => writer.write_all(&[TRUE]),
+            Value::Number(n) => {
+                if let Some(n) = n.as_u64() {
+                    writer.write_all(&[UINT])?;
+                    write_varint(n, writer)
+                } else if let Some(n) = n.as_i64() {
+                    writer.write_all(&[NEG_INT])?;
+                    // n is negative, so -(n + 1) doesn't overflow
+                    write_varint(-(n + 1) as u64, writer)
+                } else {
+                    writer.write_all(&[FLOAT])?;
+                    let f = n.as_f64().unwrap_or_default();
+                    writer.write_all(&f.to_le_bytes())
+                }
+            }
+            Value::String(s) => {
+                writer.write_all(&[STRING])?;
+                write_str(s, writer)
+            }
+            Value::Array(a) => {
+                writer.write_all(&[ARRAY])?;
+                write_varint(a.len() as u64, writer)?;
+                a.iter().try_for_each(|v| write_value(v, writer))
+            }
+            Value::Object(o) => {
+                writer.write_all(&[OBJECT])?;
+                write_varint(o.len() as u64, writer)?;
+                o.iter().try_for_each(|(k, v)| {
+                    write_str(k, writer)?;
+                    write_value(v, writer)
+                })
+            }
+        }
+    }
+
+    fn read_bytes<'a>(
+        buf: &mut &'a [u8],
+        len: usize,
+    ) -> Result<&'a [u8]> {
+        if buf.len() < len {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "unexpected end of compact value",
+            ));
+        }
+        let (bytes, rest) = buf.split_at(len);
+        *buf = rest;
+        Ok(bytes)
+    }
+
+    fn read_varint(buf: &mut &[u8]) -> Result<u64> {
+        let mut n: u64 = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = read_bytes(buf, 1)?[0];
+            let bits = (byte & 0x7f) as u64;
+            if shift == 63 && bits > 1 {
+                break;
+            }
+            n |= bits << shift;
+            if byte & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+        Err(invalid("varint overflow".into()))
+    }
+
+    /// Reads a length, which can't be larger than the remaining bytes
+    /// as each item takes at least one byte.
+    fn read_len(buf: &mut &[u8]) -> Result<usize> {
+        let len = read_varint(buf)?;
+        if len > buf.len() as u64 {
+            return Err(invalid(format!(
+                "length {} exceeds the remaining {} bytes",
+                len,
+                buf.len()
+            )));
+        }
+        Ok(len as usize)
+    }
+
+    fn read_string(buf: &mut &[u8]) -> Result<String> {
+        let len = read_len(buf)?;
+        let bytes = read_bytes(buf, len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|err| invalid(err.to_string()))
+    }
+
+    pub fn read_value(
+        buf: &mut &[u8],
+        depth: usize,
+    ) -> Result<Value> {
+        let tag = read_bytes(buf, 1)?[0];
+        let v = match tag {
+            NULL => Value::Null,
+            FALSE => Value::Bool(false),
+            TRUE => Value::Bool(true),
+            UINT => Value::Number(read_varint(buf)?.into()),
+            NEG_INT => {
+                let n = read_varint(buf)?;
+                if n > i64::MAX as u64 {
+                    return Err(invalid("negative integer overflow".into()));
+                }
+                Value::Number((-(n as i64) - 1).into())
+            }
+            FLOAT => {
+                let mut bytes = [0u8; 8];
+                bytes.copy_from_slice(read_bytes(buf, 8)?);
+                let f = f64::from_le_bytes(bytes);
+                Value::Number(
+                    Number::from_f64(f).ok_or_else(|| invalid(format!("invalid float {}", f)))?,
+                )
+            }
+            STRING => Value::String(read_string(buf)?),
+            ARRAY | OBJECT if depth == 0 => {
+                return Err(invalid("compact value is too deeply nested".into()));
+            }
+            ARRAY => {
+                let len = read_len(buf)?;
+                let mut a = Vec::with_capacity(len);
+                for _ in 0..len {
+                    a.push(read_value(buf, depth - 1)?);
+                }
+                Value::Array(a)
+            }
+            OBJECT => {
+                let len = read_len(buf)?;
+                let mut o = Map::new();
+                for _ in 0..len {
+                    let k = read_string(buf)?;
+                    let v = read_value(buf, depth - 1)?;
+                    o.insert(k, v);
+                }
+                Value::Object(o)
+            }
+            tag => return Err(invalid(format!("unknown compact value tag {}", tag))),
+        };
+        Ok(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::serde_json::json;
+
+    type Small = BoundedValue<16, 2>;
+
+    #[test]
+    fn limits() {
+        let ok: Value = json!({"a": [1]}).into();
+        assert_eq!(ok.depth(), 2);
+        assert_eq!(ok.json_size(), 9);
+        assert!(Small::new(ok).is_ok());
+
+        assert_eq!(
+            Small::new(json!([[[]]]).into()),
+            Err(ValueLimitError::TooDeep { depth: 3, max: 2 })
+        );
+        assert_eq!(
+            Small::new(json!("0123456789abcdef").into()),
+            Err(ValueLimitError::TooLarge { size: 18, max: 16 })
+        );
+    }
+
+    #[test]
+    fn deserialization() {
+        let err = serde_json::from_str::<Small>("[[[1]]]").unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("JSON value has depth 3 but at most 2 is allowed"));
+        assert!(serde_json::from_str::<Small>("[[1]]").is_ok());
+
+        // fails before reaching serde_json's recursion limit
+        let deep = format!("{}{}", "[".repeat(1000), "]".repeat(1000));
+        let err = serde_json::from_str::<Small>(&deep).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("JSON value has depth 3 but at most 2 is allowed"));
+
+        // fails at the first item over the limit
+        let large = format!("[{}]", vec!["1"; 1000].join(","));
+        let err = serde_json::from_str::<Small>(&large).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("JSON value has 17 bytes but at most 16 are allowed"));
+
+        // the counted size is the same as the size of the value
+        for json in [
+            json!({"a": [1, -2, 1.5, null, true, false]}),
+            json!("\"\\\n\u{1}é"),
+        ] {
+            use near_sdk::serde::de::DeserializeSeed;
+
+            let text = serde_json::to_string(&json).unwrap();
+            let parse = |max_bytes| {
+                let mut size = 0;
+                let seed = LimitedSeed {
+                    depth: 0,
+                    max_depth: 2,
+                    max_bytes,
+                    size: &mut size,
+                };
+                let value = seed.deserialize(&mut serde_json::Deserializer::from_str(&text));
+                value.map(|value| (value, size))
+            };
+            assert_eq!(parse(text.len()).unwrap(), (json, text.len()));
+            assert!(parse(text.len() - 1).is_err());
+        }
+
+        let large: Value = json!("0123456789abcdef").into();
+        let bytes = large.try_to_vec().unwrap();
+        let err = Small::try_from_slice(&bytes).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "JSON value has 18 bytes but at most 16 are allowed"
+        );
+
+        let ok = Small::new(json!([1]).into()).unwrap();
+        let bytes = ok.try_to_vec().unwrap();
+        assert_eq!(Small::try_from_slice(&bytes).unwrap(), ok);
+    }
+
+    #[test]
+    fn compact() {
+        let value: Value = json!({
+            "null": null,
+            "bools": [true, false],
+            "numbers": [0, 300, u64::MAX, -1, i64::MIN, 1.5],
+            "string": "abc",
+            "nested": {"a": [{"b": []}]},
+        })
+        .into();
+
+        let compact = CompactValue::from(value.clone());
+        let bytes = compact.try_to_vec().unwrap();
+        assert!(bytes.len() < value.try_to_vec().unwrap().len());
+        assert_eq!(&bytes[..5], &[0xff, 0xff, 0xff, 0xff, 1]);
+        assert_eq!(CompactValue::try_from_slice(&bytes).unwrap(), compact);
+
+        // legacy JSON text
+        let legacy = value.try_to_vec().unwrap();
+        assert_eq!(CompactValue::try_from_slice(&legacy).unwrap(), compact);
+
+        // errors
+        let mut unknown_version = bytes.clone();
+        unknown_version[4] = 2;
+        assert!(CompactValue::try_from_slice(&unknown_version).is_err());
+        assert!(CompactValue::try_from_slice(&bytes[..bytes.len() - 1]).is_err());
+        assert!(CompactValue::try_from_slice(&[0xff, 0xff, 0xff, 0xff, 1, 7, 0xff]).is_err());
+
+        let mut deep = vec![0xff, 0xff, 0xff, 0xff, 1];
+        deep.extend([7, 1].repeat(CompactValue::MAX_DEPTH + 1));
+        deep.push(0);
+        assert_eq!(
+            CompactValue::try_from_slice(&deep).unwrap_err().to_string(),
+            "compact value is too deeply nested"
+        );
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(crate = "near_sdk::serde")]
+    struct Extra {
+        color: String,
+        size: Option<u8>,
+    }
+
+    #[test]
+    fn typed() {
+        let extra = Extra {
+            color: "red".into(),
+            size: None,
+        };
+        let value = Value::from_typed(&extra).unwrap();
+        assert_eq!(value["color"], json!("red"));
+        assert_eq!(value.to_typed::<Extra>().unwrap(), extra);
+        assert!(value.to_typed::<u8>().is_err());
+        assert!(Value::default().is_null());
+
+        // same storage format as a plain value
+        let bytes = value.try_to_vec().unwrap();
+        let mut typed = TypedValue::<Extra>::try_from_slice(&bytes).unwrap();
+        assert_eq!(typed.get().unwrap(), extra);
+        assert_eq!(typed.try_to_vec().unwrap(), bytes);
+
+        typed
+            .set(&Extra {
+                color: "blue".into(),
+                size: Some(2),
+            })
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(&typed).unwrap(),
+            json!({"color": "blue", "size": 2})
+        );
+
+        assert!(TypedValue::<Extra>::try_from(Value::from(json!({"size": 1}))).is_err());
+        assert!(serde_json::from_value::<TypedValue<Extra>>(json!({"size": 1})).is_err());
+
+        // unchecked on borsh, so legacy records can still be read
+        let legacy = Value::from(json!({"size": 1})).try_to_vec().unwrap();
+        let legacy = TypedValue::<Extra>::try_from_slice(&legacy).unwrap();
+        assert!(legacy.get().is_err());
+    }
+}
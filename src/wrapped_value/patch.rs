@@ -0,0 +1,356 @@
+//! JSON Pointer (RFC 6901) and JSON Merge Patch (RFC 7396) editing of a
+//! [`Value`], and the NEP-297 event of an applied patch.
+
+use super::Value;
+use near_sdk::serde::Serialize;
+use near_sdk::{env, serde_json};
+
+/// Why a JSON Pointer couldn't be used on a [`Value`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PointerError {
+    /// The pointer is neither empty nor starts with `/`.
+    Invalid(String),
+    /// Nothing exists at the pointer (or at it's parent, when setting).
+    NotFound(String),
+    /// An array was indexed by something other than a valid index.
+    InvalidIndex(String),
+    /// The pointer goes through something that is neither an array nor an
+    /// object.
+    NotAContainer(String),
+}
+
+impl std::fmt::Display for PointerError {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        match self {
+            Self::Invalid(p) => write!(f, "invalid JSON pointer \"{}\"", p),
+            Self::NotFound(p) => write!(f, "no value at \"{}\"", p),
+            Self::InvalidIndex(p) => write!(f, "invalid array index at \"{}\"", p),
+            Self::NotAContainer(p) => write!(f, "\"{}\" is neither an array nor an object", p),
+        }
+    }
+}
+
+impl std::error::Error for PointerError {}
+
+/// The NEP-297 event of an applied JSON Merge Patch, so that indexers can
+/// follow the changes of a [`Value`] without reading all of it.
+///
+/// The patch is the event's `data`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PatchEvent<'a> {
+    pub standard: &'a str,
+    pub version: &'a str,
+    pub event: &'a str,
+    pub data: &'a Value,
+}
+
+impl PatchEvent<'_> {
+    /// The log of the event, as `EVENT_JSON:` followed by the event's JSON.
+    pub fn to_log(&self) -> String {
+        format!(
+            "EVENT_JSON:{}",
+            serde_json::to_string(self).expect("the event should serialize")
+        )
+    }
+
+    /// Logs the event.
+    pub fn emit(&self) {
+        env::log_str(&self.to_log())
+    }
+}
+
+/// Splits the pointer into it's parent and it's last (unescaped) token.
+///
+/// Returns `None` for the empty pointer, which refers to the whole value.
+fn split_last(pointer: &str) -> Result<Option<(&str, String)>, PointerError> {
+    if pointer.is_empty() {
+        return Ok(None);
+    }
+    if !pointer.starts_with('/') {
+        return Err(PointerError::Invalid(pointer.to_string()));
+    }
+    let (parent, token) = pointer.rsplit_once('/').unwrap_or_default();
+    Ok(Some((parent, token.replace("~1", "/").replace("~0", "~"))))
+}
+
+/// Parses an array index, which must not have leading zeros.
+fn parse_index(
+    token: &str,
+    pointer: &str,
+) -> Result<usize, PointerError> {
+    let valid = !token.is_empty()
+        && token.bytes().all(|b| b.is_ascii_digit())
+        && (token == "0" || !token.starts_with('0'));
+    if !valid {
+        return Err(PointerError::InvalidIndex(pointer.to_string()));
+    }
+    token
+        .parse()
+        .map_err(|_| PointerError::InvalidIndex(pointer.to_string()))
+}
+
+impl Value {
+    /// Gets the value at the JSON `pointer`, such as `/a/0/b`.
+    pub fn get_pointer(
+        &self,
+        pointer: &str,
+    ) -> Option<&serde_json::Value> {
+        self.inner.pointer(pointer)
+    }
+
+    /// Sets the value at the JSON `pointer`, returning the value that was
+    /// replaced, if any.
+    ///
+    /// The parent of the pointer must already exist. For arrays, an index
+    /// equal to the length (or `-`) appends the value.
+    pub fn set_pointer(
+        &mut self,
+        pointer: &str,
+        value: serde_json::Value,
+    ) -> Result<Option<serde_json::Value>, PointerError> {
+        let (parent, token) = match split_last(pointer)? {
+            Some(split) => split,
+            None => return Ok(Some(std::mem::replace(&mut self.inner, value))),
+        };
+        let container = self
+            .inner
+            .pointer_mut(parent)
+            .ok_or_else(|| PointerError::NotFound(parent.to_string()))?;
+        match container {
+            serde_json::Value::Object(o) => Ok(o.insert(token, value)),
+            serde_json::Value::Array(a) => {
+                let index = if token == "-" {
+                    a.len()
+                } else {
+                    parse_index(&token, pointer)?
+                };
+                match index {
+                    i if i < a.len() => Ok(Some(std::mem::replace(&mut a[i], value))),
+                    i if i == a.len() => {
+                        a.push(value);
+                        Ok(None)
+                    }
+                    _ => Err(PointerError::InvalidIndex(pointer.to_string())),
+                }
+            }
+            _ => Err(PointerError::NotAContainer(parent.to_string())),
+        }
+    }
+
+    /// Removes the value at the JSON `pointer`, returning it.
+    ///
+    /// Removing the whole value (the empty pointer) leaves `null` behind.
+    pub fn remove_pointer(
+        &mut self,
+        pointer: &str,
+    ) -> Result<serde_json::Value, PointerError> {
+        let not_found = || PointerError::NotFound(pointer.to_string());
+        let (parent, token) = match split_last(pointer)? {
+            Some(split) => split,
+            None => return Ok(self.inner.take()),
+        };
+        match self.inner.pointer_mut(parent).ok_or_else(not_found)? {
+            serde_json::Value::Object(o) => o.remove(&token).ok_or_else(not_found),
+            serde_json::Value::Array(a) => {
+                let index = parse_index(&token, pointer)?;
+                if index < a.len() {
+                    Ok(a.remove(index))
+                } else {
+                    Err(not_found())
+                }
+            }
+            _ => Err(PointerError::NotAContainer(parent.to_string())),
+        }
+    }
+
+    /// Applies a JSON Merge Patch.
+    ///
+    /// Objects in the `patch` are merged recursively, `null` removes a
+    /// member and anything else replaces the target.
+    pub fn merge_patch(
+        &mut self,
+        patch: &Value,
+    ) {
+        fn merge(
+            target: &mut serde_json::Value,
+            patch: &serde_json::Value,
+        ) {
+            let patch = match patch {
+                serde_json::Value::Object(patch) => patch,
+                _ => {
+                    *target = patch.clone();
+                    return;
+                }
+            };
+            if !target.is_object() {
+                *target = serde_json::Value::Object(Default::default());
+            }
+            if let serde_json::Value::Object(target) = target {
+                for (key, value) in patch {
+                    if value.is_null() {
+                        target.remove(key);
+                    } else {
+                        merge(
+                            target.entry(key.clone()).or_insert(serde_json::Value::Null),
+                            value,
+                        );
+                    }
+                }
+            }
+        }
+        merge(&mut self.inner, &patch.inner)
+    }
+
+    /// Applies a JSON Merge Patch, like [`merge_patch`](Self::merge_patch),
+    /// and emits it as the `event` of the `standard`, at it's `version`.
+    pub fn merge_patch_and_emit(
+        &mut self,
+        patch: &Value,
+        standard: &str,
+        version: &str,
+        event: &str,
+    ) {
+        self.merge_patch(patch);
+        PatchEvent {
+            standard,
+            version,
+            event,
+            data: patch,
+        }
+        .emit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::serde_json::json;
+    use near_sdk::test_utils::get_logs;
+
+    #[test]
+    fn pointer() {
+        let mut v: Value = json!({"a": {"b~c/d": [1, 2]}}).into();
+        assert_eq!(v.get_pointer("/a/b~0c~1d/1"), Some(&json!(2)));
+
+        assert_eq!(v.set_pointer("/a/b~0c~1d/0", json!(0)), Ok(Some(json!(1))));
+        assert_eq!(v.set_pointer("/a/b~0c~1d/-", json!(3)), Ok(None));
+        assert_eq!(v.set_pointer("/a/e", json!("e")), Ok(None));
+        assert_eq!(
+            serde_json::Value::from(v.clone()),
+            json!({"a": {"b~c/d": [0, 2, 3], "e": "e"}})
+        );
+
+        assert_eq!(v.remove_pointer("/a/b~0c~1d/1"), Ok(json!(2)));
+        assert_eq!(v.remove_pointer("/a/e"), Ok(json!("e")));
+        assert_eq!(
+            serde_json::Value::from(v.clone()),
+            json!({"a": {"b~c/d": [0, 3]}})
+        );
+
+        assert_eq!(
+            v.set_pointer("a", json!(1)),
+            Err(PointerError::Invalid("a".into()))
+        );
+        assert_eq!(
+            v.set_pointer("/x/y", json!(1)),
+            Err(PointerError::NotFound("/x".into()))
+        );
+        assert_eq!(
+            v.set_pointer("/a/b~0c~1d/01", json!(1)),
+            Err(PointerError::InvalidIndex("/a/b~0c~1d/01".into()))
+        );
+        assert_eq!(
+            v.set_pointer("/a/b~0c~1d/0/x", json!(1)),
+            Err(PointerError::NotAContainer("/a/b~0c~1d/0".into()))
+        );
+        assert_eq!(
+            v.remove_pointer("/a/b~0c~1d/0/x"),
+            Err(PointerError::NotAContainer("/a/b~0c~1d/0".into()))
+        );
+        assert_eq!(
+            v.remove_pointer("/a/b~0c~1d/5"),
+            Err(PointerError::NotFound("/a/b~0c~1d/5".into()))
+        );
+
+        assert_eq!(
+            v.set_pointer("", json!(1)),
+            Ok(Some(json!({"a": {"b~c/d": [0, 3]}})))
+        );
+        assert_eq!(v.remove_pointer(""), Ok(json!(1)));
+        assert_eq!(serde_json::Value::from(v), json!(null));
+    }
+
+    /// Examples from RFC 7396.
+    #[test]
+    fn merge_patch() {
+        let cases = [
+            (json!({"a": "b"}), json!({"a": "c"}), json!({"a": "c"})),
+            (
+                json!({"a": "b"}),
+                json!({"b": "c"}),
+                json!({"a": "b", "b": "c"}),
+            ),
+            (json!({"a": "b"}), json!({ "a": null }), json!({})),
+            (
+                json!({"a": "b", "b": "c"}),
+                json!({ "a": null }),
+                json!({"b": "c"}),
+            ),
+            (json!({"a": ["b"]}), json!({"a": "c"}), json!({"a": "c"})),
+            (json!({"a": "c"}), json!({"a": ["b"]}), json!({"a": ["b"]})),
+            (
+                json!({"a": {"b": "c"}}),
+                json!({"a": {"b": "d", "c": null}}),
+                json!({"a": {"b": "d"}}),
+            ),
+            (
+                json!({"a": [{"b": "c"}]}),
+                json!({"a": [1]}),
+                json!({"a": [1]}),
+            ),
+            (json!(["a", "b"]), json!(["c", "d"]), json!(["c", "d"])),
+            (json!({"a": "b"}), json!(["c"]), json!(["c"])),
+            (json!({"a": "foo"}), json!(null), json!(null)),
+            (json!({"a": "foo"}), json!("bar"), json!("bar")),
+            (
+                json!({ "e": null }),
+                json!({"a": 1}),
+                json!({"e": null, "a": 1}),
+            ),
+            (
+                json!([1, 2]),
+                json!({"a": "b", "c": null}),
+                json!({"a": "b"}),
+            ),
+            (
+                json!({}),
+                json!({"a": {"bb": {"ccc": null}}}),
+                json!({"a": {"bb": {}}}),
+            ),
+        ];
+        for (target, patch, expected) in cases {
+            let mut target: Value = target.into();
+            target.merge_patch(&patch.into());
+            assert_eq!(serde_json::Value::from(target), expected);
+        }
+    }
+
+    #[test]
+    fn event() {
+        let mut v: Value = json!({"name": "a", "tags": ["x"]}).into();
+        let patch: Value = json!({"name": "b", "tags": null}).into();
+        v.merge_patch_and_emit(&patch, "nep171", "1.0.0", "metadata_patch");
+
+        assert_eq!(serde_json::Value::from(v), json!({"name": "b"}));
+        assert_eq!(
+            get_logs(),
+            [
+                r#"EVENT_JSON:{"standard":"nep171","version":"1.0.0","event":"metadata_patch","data":{"name":"b","tags":null}}"#
+            ]
+        );
+    }
+}
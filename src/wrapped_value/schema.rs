@@ -0,0 +1,822 @@
+//! Validation of a [`Value`] against a JSON schema.
+//!
+//! Supports the draft-07 keywords that `schemars` can represent, except
+//! for `format`, which is only informative. A `$ref` must be a JSON Pointer
+//! into the root schema, such as `#/definitions/Name` or `#`.
+
+use super::Value;
+use near_sdk::{
+    schemars::{
+        schema::{InstanceType, RootSchema, Schema, SchemaObject, SingleOrVec},
+        JsonSchema,
+    },
+    serde::Deserialize,
+    serde_json,
+};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Maximum depth of `$ref` and subschema resolution, so that recursive
+/// schemas can't recurse forever.
+const MAX_DEPTH: usize = 64;
+
+/// Where, and why, a [`Value`] doesn't follow a schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// JSON Pointer (RFC 6901) to the offending part of the value.
+    pub pointer: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        // the empty pointer is the whole value, while `/` would be the
+        // empty-string key
+        let pointer = if self.pointer.is_empty() {
+            "(root)"
+        } else {
+            &self.pointer
+        };
+        write!(f, "{}: {}", pointer, self.message)
+    }
+}
+
+impl Value {
+    /// Validates the value against the `schema`, which may have been
+    /// generated from a Rust type or deserialized from a stored JSON schema.
+    ///
+    /// Returns all of the violations that were found.
+    ///
+    /// To validate many values against the same schema, see
+    /// [`CompiledSchema`], which keeps it's compiled patterns and resolved
+    /// references.
+    pub fn validate(
+        &self,
+        schema: &RootSchema,
+    ) -> Result<(), Vec<Violation>> {
+        validate(
+            &self.inner,
+            schema,
+            &RegexCache::default(),
+            &RefCache::default(),
+        )
+    }
+
+    /// Validates the value against the schema of `T`.
+    pub fn validate_as<T: JsonSchema>(&self) -> Result<(), Vec<Violation>> {
+        self.validate(&near_sdk::schemars::schema_for!(T))
+    }
+}
+
+/// A JSON schema with the regexes of it's patterns compiled, and it's
+/// references resolved, once, on their first use, so it can validate many
+/// values.
+#[derive(Debug)]
+pub struct CompiledSchema {
+    root: RootSchema,
+    regexes: RegexCache,
+    refs: RefCache,
+}
+
+impl CompiledSchema {
+    pub fn new(root: RootSchema) -> Self {
+        Self {
+            root,
+            regexes: RegexCache::default(),
+            refs: RefCache::default(),
+        }
+    }
+
+    /// The schema of `T`.
+    pub fn of<T: JsonSchema>() -> Self {
+        Self::new(near_sdk::schemars::schema_for!(T))
+    }
+
+    pub fn root(&self) -> &RootSchema {
+        &self.root
+    }
+
+    /// Same as [`Value::validate`].
+    pub fn validate(
+        &self,
+        value: &Value,
+    ) -> Result<(), Vec<Violation>> {
+        validate(&value.inner, &self.root, &self.regexes, &self.refs)
+    }
+}
+
+/// Compiled regexes (or their compilation errors), by pattern.
+#[derive(Debug, Default)]
+struct RegexCache(RefCell<HashMap<String, Result<regex::Regex, String>>>);
+
+impl RegexCache {
+    fn get(
+        &self,
+        pattern: &str,
+    ) -> Result<regex::Regex, String> {
+        // cloning a regex is cheap, as it's compiled form is shared
+        self.0
+            .borrow_mut()
+            .entry(pattern.to_string())
+            .or_insert_with(|| regex::Regex::new(pattern).map_err(|err| err.to_string()))
+            .clone()
+    }
+}
+
+/// Resolved schemas (or why they couldn't be resolved), by `$ref`.
+#[derive(Debug, Default)]
+struct RefCache {
+    /// The JSON of the root schema, which the references point into.
+    root: RefCell<Option<Value>>,
+    schemas: RefCell<HashMap<String, Result<Rc<Schema>, String>>>,
+}
+
+impl RefCache {
+    fn get(
+        &self,
+        root: &RootSchema,
+        reference: &str,
+    ) -> Result<Rc<Schema>, String> {
+        self.schemas
+            .borrow_mut()
+            .entry(reference.to_string())
+            .or_insert_with(|| self.resolve(root, reference))
+            .clone()
+    }
+
+    fn resolve(
+        &self,
+        root: &RootSchema,
+        reference: &str,
+    ) -> Result<Rc<Schema>, String> {
+        let pointer = reference
+            .strip_prefix('#')
+            .ok_or_else(|| format!("unsupported schema reference {}", reference))?;
+        let mut json = self.root.borrow_mut();
+        if json.is_none() {
+            let root =
+                Value::from_typed(root).map_err(|err| format!("invalid root schema: {}", err))?;
+            *json = Some(root);
+        }
+        let schema = json
+            .as_ref()
+            .and_then(|json| json.get_pointer(pointer))
+            .ok_or_else(|| format!("unknown schema reference {}", reference))?;
+        Schema::deserialize(schema)
+            .map(Rc::new)
+            .map_err(|err| format!("invalid schema at {}: {}", reference, err))
+    }
+}
+
+fn validate(
+    v: &serde_json::Value,
+    schema: &RootSchema,
+    regexes: &RegexCache,
+    refs: &RefCache,
+) -> Result<(), Vec<Violation>> {
+    let mut validator = Validator {
+        root: schema,
+        regexes,
+        refs,
+        violations: vec![],
+    };
+    validator.validate_object_schema(v, &schema.schema, &mut String::new(), 0);
+    if validator.violations.is_empty() {
+        Ok(())
+    } else {
+        Err(validator.violations)
+    }
+}
+
+/// Appends the escaped `token` into the JSON `pointer`.
+fn push_token(
+    pointer: &mut String,
+    token: &str,
+) {
+    pointer.push('/');
+    pointer.push_str(&token.replace('~', "~0").replace('/', "~1"));
+}
+
+fn type_name(v: &serde_json::Value) -> &'static str {
+    match v {
+        serde_json::Value::Null => "null",
+        serde_json::Value::Bool(_) => "boolean",
+        serde_json::Value::Number(_) => "number",
+        serde_json::Value::String(_) => "string",
+        serde_json::Value::Array(_) => "array",
+        serde_json::Value::Object(_) => "object",
+    }
+}
+
+fn has_type(
+    v: &serde_json::Value,
+    t: &InstanceType,
+) -> bool {
+    match (t, v) {
+        (InstanceType::Null, serde_json::Value::Null)
+        | (InstanceType::Boolean, serde_json::Value::Bool(_))
+        | (InstanceType::Number, serde_json::Value::Number(_))
+        | (InstanceType::String, serde_json::Value::String(_))
+        | (InstanceType::Array, serde_json::Value::Array(_))
+        | (InstanceType::Object, serde_json::Value::Object(_)) => true,
+        (InstanceType::Integer, serde_json::Value::Number(n)) => {
+            n.is_i64() || n.is_u64() || n.as_f64().map_or(false, |f| f.fract() == 0.0)
+        }
+        _ => false,
+    }
+}
+
+struct Validator<'s> {
+    root: &'s RootSchema,
+    regexes: &'s RegexCache,
+    refs: &'s RefCache,
+    violations: Vec<Violation>,
+}
+
+impl<'s> Validator<'s> {
+    fn violation(
+        &mut self,
+        pointer: &str,
+        message: String,
+    ) {
+        self.violations.push(Violation {
+            pointer: pointer.to_string(),
+            message,
+        });
+    }
+
+    /// Whether `v` is valid against `schema`, without recording violations.
+    fn is_valid(
+        &self,
+        v: &serde_json::Value,
+        schema: &Schema,
+        depth: usize,
+    ) -> bool {
+        let mut validator = Validator {
+            root: self.root,
+            regexes: self.regexes,
+            refs: self.refs,
+            violations: vec![],
+        };
+        validator.validate(v, schema, &mut String::new(), depth);
+        validator.violations.is_empty()
+    }
+
+    fn validate(
+        &mut self,
+        v: &serde_json::Value,
+        schema: &Schema,
+        pointer: &mut String,
+        depth: usize,
+    ) {
+        match schema {
+            Schema::Bool(true) => {}
+            Schema::Bool(false) => self.violation(pointer, "no value is allowed".into()),
+            Schema::Object(schema) => self.validate_object_schema(v, schema, pointer, depth),
+        }
+    }
+
+    fn validate_object_schema(
+        &mut self,
+        v: &serde_json::Value,
+        schema: &SchemaObject,
+        pointer: &mut String,
+        depth: usize,
+    ) {
+        if depth > MAX_DEPTH {
+            return self.violation(pointer, "schema is too deeply nested".into());
+        }
+
+        if let Some(reference) = &schema.reference {
+            match self.refs.get(self.root, reference) {
+                Ok(referenced) => self.validate(v, &referenced, pointer, depth + 1),
+                Err(err) => self.violation(pointer, err),
+            }
+        }
+
+        self.validate_generic(v, schema, pointer);
+        self.validate_subschemas(v, schema, pointer, depth);
+        match v {
+            serde_json::Value::Number(n) => self.validate_number(n, schema, pointer),
+            serde_json::Value::String(s) => self.validate_string(s, schema, pointer),
+            serde_json::Value::Array(a) => self.validate_array(a, schema, pointer, depth),
+            serde_json::Value::Object(o) => self.validate_object(o, schema, pointer, depth),
+            _ => {}
+        }
+    }
+
+    fn validate_generic(
+        &mut self,
+        v: &serde_json::Value,
+        schema: &SchemaObject,
+        pointer: &str,
+    ) {
+        if let Some(types) = &schema.instance_type {
+            let types: Vec<&InstanceType> = match types {
+                SingleOrVec::Single(t) => vec![t],
+                SingleOrVec::Vec(ts) => ts.iter().collect(),
+            };
+            if !types.iter().any(|t| has_type(v, t)) {
+                let expected: Vec<String> = types
+                    .iter()
+                    .map(|t| {
+                        serde_json::to_value(t)
+                            .ok()
+                            .and_then(|t| t.as_str().map(str::to_string))
+                            .unwrap_or_default()
+                    })
+                    .collect();
+                self.violation(
+                    pointer,
+                    format!("expected {}, got {}", expected.join(" or "), type_name(v)),
+                );
+            }
+        }
+        if let Some(values) = &schema.enum_values {
+            if !values.contains(v) {
+                self.violation(pointer, format!("{} is not one of the allowed values", v));
+            }
+        }
+        if let Some(value) = &schema.const_value {
+            if value != v {
+                self.violation(pointer, format!("expected {}, got {}", value, v));
+            }
+        }
+    }
+
+    fn validate_subschemas(
+        &mut self,
+        v: &serde_json::Value,
+        schema: &SchemaObject,
+        pointer: &mut String,
+        depth: usize,
+    ) {
+        let sub = match &schema.subschemas {
+            Some(sub) => sub,
+            None => return,
+        };
+        for schema in sub.all_of.iter().flatten() {
+            self.validate(v, schema, pointer, depth + 1);
+        }
+        if let Some(any_of) = &sub.any_of {
+            if !any_of.iter().any(|s| self.is_valid(v, s, depth + 1)) {
+                self.violation(pointer, "doesn't match any of the schemas in anyOf".into());
+            }
+        }
+        if let Some(one_of) = &sub.one_of {
+            let matches = one_of
+                .iter()
+                .filter(|s| self.is_valid(v, s, depth + 1))
+                .count();
+            if matches != 1 {
+                self.violation(
+                    pointer,
+                    format!(
+                        "must match exactly one of the schemas in oneOf, matched {}",
+                        matches
+                    ),
+                );
+            }
+        }
+        if let Some(not) = &sub.not {
+            if self.is_valid(v, not, depth + 1) {
+                self.violation(pointer, "must not match the schema in not".into());
+            }
+        }
+        if let Some(if_schema) = &sub.if_schema {
+            let branch = if self.is_valid(v, if_schema, depth + 1) {
+                &sub.then_schema
+            } else {
+                &sub.else_schema
+            };
+            if let Some(branch) = branch {
+                self.validate(v, branch, pointer, depth + 1);
+            }
+        }
+    }
+
+    fn validate_number(
+        &mut self,
+        n: &serde_json::Number,
+        schema: &SchemaObject,
+        pointer: &str,
+    ) {
+        let validation = match &schema.number {
+            Some(validation) => validation,
+            None => return,
+        };
+        let f = n.as_f64().unwrap_or_default();
+        if let Some(max) = validation.maximum {
+            if f > max {
+                self.violation(
+                    pointer,
+                    format!("{} is greater than the maximum {}", n, max),
+                );
+            }
+        }
+        if let Some(max) = validation.exclusive_maximum {
+            if f >= max {
+                self.violation(
+                    pointer,
+                    format!("{} is not less than the exclusive maximum {}", n, max),
+                );
+            }
+        }
+        if let Some(min) = validation.minimum {
+            if f < min {
+                self.violation(pointer, format!("{} is less than the minimum {}", n, min));
+            }
+        }
+        if let Some(min) = validation.exclusive_minimum {
+            if f <= min {
+                self.violation(
+                    pointer,
+                    format!("{} is not greater than the exclusive minimum {}", n, min),
+                );
+            }
+        }
+        if let Some(multiple) = validation.multiple_of {
+            if multiple > 0.0 && (f / multiple).fract() != 0.0 {
+                self.violation(pointer, format!("{} is not a multiple of {}", n, multiple));
+            }
+        }
+    }
+
+    fn validate_string(
+        &mut self,
+        s: &str,
+        schema: &SchemaObject,
+        pointer: &str,
+    ) {
+        let validation = match &schema.string {
+            Some(validation) => validation,
+            None => return,
+        };
+        let len = s.chars().count();
+        if let Some(max) = validation.max_length {
+            if len > max as usize {
+                self.violation(
+                    pointer,
+                    format!(
+                        "string has {} characters, more than the maximum {}",
+                        len, max
+                    ),
+                );
+            }
+        }
+        if let Some(min) = validation.min_length {
+            if len < min as usize {
+                self.violation(
+                    pointer,
+                    format!(
+                        "string has {} characters, less than the minimum {}",
+                        len, min
+                    ),
+                );
+            }
+        }
+        if let Some(pattern) = &validation.pattern {
+            match self.regexes.get(pattern) {
+                Ok(regex) if regex.is_match(s) => {}
+                Ok(_) => self.violation(
+                    pointer,
+                    format!("string doesn't match the pattern {}", pattern),
+                ),
+                Err(err) => {
+                    self.violation(pointer, format!("invalid pattern {}: {}", pattern, err))
+                }
+            }
+        }
+    }
+
+    fn validate_array(
+        &mut self,
+        a: &[serde_json::Value],
+        schema: &SchemaObject,
+        pointer: &mut String,
+        depth: usize,
+    ) {
+        let validation = match &schema.array {
+            Some(validation) => validation,
+            None => return,
+        };
+        if let Some(max) = validation.max_items {
+            if a.len() > max as usize {
+                self.violation(
+                    pointer,
+                    format!("array has {} items, more than the maximum {}", a.len(), max),
+                );
+            }
+        }
+        if let Some(min) = validation.min_items {
+            if a.len() < min as usize {
+                self.violation(
+                    pointer,
+                    format!("array has {} items, less than the minimum {}", a.len(), min),
+                );
+            }
+        }
+        if validation.unique_items == Some(true) {
+            for (i, item) in a.iter().enumerate() {
+                if a[..i].contains(item) {
+                    let len = pointer.len();
+                    push_token(pointer, &i.to_string());
+                    self.violation(pointer, "array items must be unique".into());
+                    pointer.truncate(len);
+                }
+            }
+        }
+        if let Some(contains) = &validation.contains {
+            if !a
+                .iter()
+                .any(|item| self.is_valid(item, contains, depth + 1))
+            {
+                self.violation(
+                    pointer,
+                    "no array item matches the schema in contains".into(),
+                );
+            }
+        }
+
+        for (i, item) in a.iter().enumerate() {
+            let item_schema = match &validation.items {
+                None => None,
+                Some(SingleOrVec::Single(schema)) => Some(schema.as_ref()),
+                Some(SingleOrVec::Vec(schemas)) => {
+                    schemas.get(i).or(validation.additional_items.as_deref())
+                }
+            };
+            if let Some(item_schema) = item_schema {
+                let len = pointer.len();
+                push_token(pointer, &i.to_string());
+                self.validate(item, item_schema, pointer, depth + 1);
+                pointer.truncate(len);
+            }
+        }
+    }
+
+    fn validate_object(
+        &mut self,
+        o: &serde_json::Map<String, serde_json::Value>,
+        schema: &SchemaObject,
+        pointer: &mut String,
+        depth: usize,
+    ) {
+        let validation = match &schema.object {
+            Some(validation) => validation,
+            None => return,
+        };
+        if let Some(max) = validation.max_properties {
+            if o.len() > max as usize {
+                self.violation(
+                    pointer,
+                    format!(
+                        "object has {} properties, more than the maximum {}",
+                        o.len(),
+                        max
+                    ),
+                );
+            }
+        }
+        if let Some(min) = validation.min_properties {
+            if o.len() < min as usize {
+                self.violation(
+                    pointer,
+                    format!(
+                        "object has {} properties, less than the minimum {}",
+                        o.len(),
+                        min
+                    ),
+                );
+            }
+        }
+        for required in &validation.required {
+            if !o.contains_key(required) {
+                self.violation(
+                    pointer,
+                    format!("missing required property \"{}\"", required),
+                );
+            }
+        }
+
+        let patterns: Vec<(Option<regex::Regex>, &Schema)> = validation
+            .pattern_properties
+            .iter()
+            .map(|(pattern, schema)| (self.regexes.get(pattern).ok(), schema))
+            .collect();
+
+        for (key, value) in o {
+            let len = pointer.len();
+            push_token(pointer, key);
+
+            if let Some(names) = &validation.property_names {
+                if !self.is_valid(&serde_json::Value::String(key.clone()), names, depth + 1) {
+                    self.violation(pointer, "property name doesn't match propertyNames".into());
+                }
+            }
+
+            let mut matched = false;
+            if let Some(schema) = validation.properties.get(key) {
+                matched = true;
+                self.validate(value, schema, pointer, depth + 1);
+            }
+            for (regex, schema) in &patterns {
+                if regex.as_ref().map_or(false, |r| r.is_match(key)) {
+                    matched = true;
+                    self.validate(value, schema, pointer, depth + 1);
+                }
+            }
+            if !matched {
+                match validation.additional_properties.as_deref() {
+                    Some(Schema::Bool(false)) => {
+                        self.violation(pointer, format!("unknown property \"{}\"", key))
+                    }
+                    Some(schema) => self.validate(value, schema, pointer, depth + 1),
+                    None => {}
+                }
+            }
+
+            pointer.truncate(len);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::serde_json::json;
+
+    #[allow(dead_code)]
+    #[derive(Deserialize, JsonSchema)]
+    #[serde(crate = "near_sdk::serde")]
+    #[schemars(crate = "near_sdk::schemars")]
+    #[serde(deny_unknown_fields)]
+    struct Config {
+        name: String,
+        limit: u8,
+        tags: Vec<Tag>,
+    }
+
+    #[allow(dead_code)]
+    #[derive(Deserialize, JsonSchema)]
+    #[serde(crate = "near_sdk::serde")]
+    #[schemars(crate = "near_sdk::schemars")]
+    enum Tag {
+        A,
+        B,
+    }
+
+    #[test]
+    fn valid() {
+        let v: Value = json!({"name": "x", "limit": 3, "tags": ["A", "B"]}).into();
+        assert_eq!(v.validate_as::<Config>(), Ok(()));
+    }
+
+    #[test]
+    fn violations() {
+        let v: Value = json!({"a/b": null, "limit": -1, "tags": ["A", "C", 1]}).into();
+        let violations = v.validate_as::<Config>().unwrap_err();
+        let pointers: Vec<&str> = violations.iter().map(|v| v.pointer.as_str()).collect();
+        assert_eq!(
+            pointers,
+            vec!["", "/a~1b", "/limit", "/tags/1", "/tags/2", "/tags/2"]
+        );
+        assert_eq!(
+            violations[0].to_string(),
+            "(root): missing required property \"name\""
+        );
+        assert_eq!(violations[1].message, "unknown property \"a/b\"");
+        assert_eq!(violations[2].message, "-1 is less than the minimum 0");
+        assert_eq!(
+            violations[3].message,
+            "\"C\" is not one of the allowed values"
+        );
+        assert_eq!(violations[4].message, "expected string, got number");
+    }
+
+    #[test]
+    fn stored_schema() {
+        let schema: RootSchema = serde_json::from_value(json!({
+            "type": "array",
+            "items": {"type": "integer", "minimum": 0},
+            "maxItems": 2,
+            "uniqueItems": true,
+        }))
+        .unwrap();
+
+        let v: Value = json!([1, 2]).into();
+        assert_eq!(v.validate(&schema), Ok(()));
+
+        let v: Value = json!([1, 1, -1.5]).into();
+        let messages: Vec<String> = v
+            .validate(&schema)
+            .unwrap_err()
+            .into_iter()
+            .map(|v| v.to_string())
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "(root): array has 3 items, more than the maximum 2",
+                "/1: array items must be unique",
+                "/2: expected integer, got number",
+                "/2: -1.5 is less than the minimum 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn compiled() {
+        let schema: RootSchema = serde_json::from_value(json!({
+            "type": "object",
+            "properties": {"name": {"type": "string", "pattern": "^[a-z]+$"}},
+            "patternProperties": {"^x-": {"type": "integer"}},
+            "definitions": {},
+        }))
+        .unwrap();
+        let schema = CompiledSchema::new(schema);
+
+        for _ in 0..2 {
+            let v: Value = json!({"name": "abc", "x-a": 1}).into();
+            assert_eq!(schema.validate(&v), Ok(()));
+            let v: Value = json!({"name": "ABC", "x-a": "1"}).into();
+            let pointers: Vec<String> = schema
+                .validate(&v)
+                .unwrap_err()
+                .into_iter()
+                .map(|v| v.pointer)
+                .collect();
+            assert_eq!(pointers, ["/name", "/x-a"]);
+        }
+        // each pattern was compiled once
+        assert_eq!(schema.regexes.0.borrow().len(), 2);
+    }
+
+    #[test]
+    fn empty_key() {
+        let v: Value = json!({"": 1}).into();
+        let schema: RootSchema = serde_json::from_value(json!({
+            "additionalProperties": {"type": "string"},
+        }))
+        .unwrap();
+        let violations = v.validate(&schema).unwrap_err();
+        assert_eq!(violations[0].to_string(), "/: expected string, got number");
+    }
+
+    #[test]
+    fn references() {
+        let schema: RootSchema = serde_json::from_value(json!({
+            "type": "object",
+            "properties": {
+                "a": {"$ref": "#/definitions/a~1b"},
+                "b": {"$ref": "#/definitions/c~0d"},
+                // not a definition, though it's last segment names one
+                "c": {"$ref": "#/properties/a"},
+                "d": {"type": "array", "items": {"$ref": "#"}},
+                "e": {"$ref": "#/definitions/missing"},
+                "f": {"$ref": "other.json#/definitions/a~1b"},
+            },
+            "definitions": {
+                "a/b": {"type": "string"},
+                "c~d": {"type": "integer"},
+                "a": {"type": "boolean"},
+            },
+        }))
+        .unwrap();
+        let schema = CompiledSchema::new(schema);
+
+        let v: Value = json!({"a": "x", "b": 1, "c": "y", "d": [{"d": [{"a": "z"}]}]}).into();
+        assert_eq!(schema.validate(&v), Ok(()));
+
+        let v: Value = json!({
+            "a": 1,
+            "b": "x",
+            "c": true,
+            "d": [{"d": [{"b": 1.5}]}],
+            "e": 1,
+            "f": 1,
+        })
+        .into();
+        let messages: Vec<String> = schema
+            .validate(&v)
+            .unwrap_err()
+            .into_iter()
+            .map(|v| v.to_string())
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "/a: expected string, got number",
+                "/b: expected integer, got string",
+                "/c: expected string, got boolean",
+                "/d/0/d/0/b: expected integer, got number",
+                "/e: unknown schema reference #/definitions/missing",
+                "/f: unsupported schema reference other.json#/definitions/a~1b",
+            ]
+        );
+    }
+}